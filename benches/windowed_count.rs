@@ -0,0 +1,93 @@
+//! The rolling window counts of every day of `tests/fixtures/small.json`, as in
+//! `user_totals_per_day.csv`.
+use chrono::NaiveDate;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hint::black_box;
+
+#[path = "../src/window.rs"]
+mod window;
+
+const WINDOW_DAYS: u64 = 365;
+
+/// The per-day data of the fixture, like in `Stats`.
+#[derive(Default)]
+struct Days {
+    day_edit_users: BTreeMap<NaiveDate, HashSet<u32>>,
+    day_user_edits: BTreeMap<NaiveDate, HashMap<u32, u64>>,
+    day_user_changesets: BTreeMap<NaiveDate, HashMap<u32, HashSet<u32>>>,
+}
+
+fn read_fixture() -> Days {
+    let objects: Vec<serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string("tests/fixtures/small.json").unwrap())
+            .unwrap();
+    let mut days = Days::default();
+    for object in objects {
+        let day = object["ts"].as_str().unwrap()[..10].parse().unwrap();
+        let uid = object["uid"].as_u64().unwrap() as u32;
+        let changeset = object["changeset"].as_u64().unwrap() as u32;
+        days.day_edit_users.entry(day).or_default().insert(uid);
+        *days
+            .day_user_edits
+            .entry(day)
+            .or_default()
+            .entry(uid)
+            .or_default() += 1;
+        days.day_user_changesets
+            .entry(day)
+            .or_default()
+            .entry(uid)
+            .or_default()
+            .insert(changeset);
+    }
+    days
+}
+
+fn windowed_count(c: &mut Criterion) {
+    let days = read_fixture();
+    let first_day = *days.day_edit_users.first_key_value().unwrap().0;
+    let last_day = *days.day_edit_users.last_key_value().unwrap().0;
+    let dates: Vec<NaiveDate> = first_day
+        .iter_days()
+        .take_while(|day| *day <= last_day)
+        .collect();
+    let window = |day: &NaiveDate| *day - chrono::Days::new(WINDOW_DAYS)..=*day;
+    c.bench_function("window_users", |b| {
+        b.iter(|| {
+            for day in dates.iter() {
+                black_box(window::window_users(days.day_edit_users.range(window(day))));
+            }
+        })
+    });
+    c.bench_function("window_user_days", |b| {
+        b.iter(|| {
+            for day in dates.iter() {
+                black_box(window::window_user_days(
+                    days.day_edit_users.range(window(day)),
+                ));
+            }
+        })
+    });
+    c.bench_function("window_user_edits", |b| {
+        b.iter(|| {
+            for day in dates.iter() {
+                black_box(window::window_user_edits(
+                    days.day_user_edits.range(window(day)),
+                ));
+            }
+        })
+    });
+    c.bench_function("window_user_changesets", |b| {
+        b.iter(|| {
+            for day in dates.iter() {
+                black_box(window::window_user_changesets(
+                    days.day_user_changesets.range(window(day)),
+                ));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, windowed_count);
+criterion_main!(benches);
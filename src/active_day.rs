@@ -0,0 +1,245 @@
+//! `--active-day-expr`: which days of a user count as edit days, from a small expression over
+//! what they did that day, like `edit_count >= 3 && has_tags`.
+//!
+//! The grammar, loosest first:
+//!
+//! ```text
+//! or      := and ("||" and)*
+//! and     := compare ("&&" compare)*
+//! compare := atom (("==" | "!=" | ">=" | "<=" | ">" | "<") atom)?
+//! atom    := number | feature | "!" atom | "(" or ")"
+//! ```
+//!
+//! Every value is a whole number. `has_tags`, comparisons, `!`, `&&` & `||` are 1 for true & 0
+//! for false, and anything but 0 is true.
+use std::fmt;
+
+/// What's known about each day of each user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Number of object versions
+    EditCount,
+    /// Number of different objects
+    DistinctObjects,
+    /// Whether any of the object versions has a tag
+    HasTags,
+}
+
+impl Feature {
+    const ALL: [(&'static str, Feature); 3] = [
+        ("edit_count", Feature::EditCount),
+        ("distinct_objects", Feature::DistinctObjects),
+        ("has_tags", Feature::HasTags),
+    ];
+}
+
+/// The features of one day of one user. Only the ones the expression uses are filled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DayFeatures {
+    pub edit_count: u64,
+    pub distinct_objects: u64,
+    pub has_tags: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(u64),
+    Feature(Feature),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Cmp(Box<Expr>, CmpOp, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, features: &DayFeatures) -> u64 {
+        match self {
+            Expr::Num(num) => *num,
+            Expr::Feature(Feature::EditCount) => features.edit_count,
+            Expr::Feature(Feature::DistinctObjects) => features.distinct_objects,
+            Expr::Feature(Feature::HasTags) => features.has_tags.into(),
+            Expr::Not(expr) => (expr.eval(features) == 0).into(),
+            Expr::And(left, right) => {
+                (left.eval(features) != 0 && right.eval(features) != 0).into()
+            }
+            Expr::Or(left, right) => (left.eval(features) != 0 || right.eval(features) != 0).into(),
+            Expr::Cmp(left, op, right) => {
+                let (left, right) = (left.eval(features), right.eval(features));
+                match op {
+                    CmpOp::Eq => left == right,
+                    CmpOp::Ne => left != right,
+                    CmpOp::Ge => left >= right,
+                    CmpOp::Le => left <= right,
+                    CmpOp::Gt => left > right,
+                    CmpOp::Lt => left < right,
+                }
+                .into()
+            }
+        }
+    }
+
+    fn uses(&self, feature: Feature) -> bool {
+        match self {
+            Expr::Num(_) => false,
+            Expr::Feature(this_feature) => *this_feature == feature,
+            Expr::Not(expr) => expr.uses(feature),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::Cmp(left, _, right) => {
+                left.uses(feature) || right.uses(feature)
+            }
+        }
+    }
+}
+
+/// A parsed `--active-day-expr`.
+#[derive(Debug, Clone)]
+pub struct ActiveDayExpr {
+    /// As it was given, to show it again
+    source: String,
+    expr: Expr,
+}
+
+impl ActiveDayExpr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parser = Parser { s, pos: 0 };
+        let expr = parser.or()?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            return Err(parser.error("expected `&&`, `||` or the end"));
+        }
+        Ok(ActiveDayExpr {
+            source: s.to_owned(),
+            expr,
+        })
+    }
+
+    /// Whether a day with these features counts.
+    pub fn is_active(&self, features: &DayFeatures) -> bool {
+        self.expr.eval(features) != 0
+    }
+
+    /// Whether this feature is in the expression, i.e. needs to be calculated.
+    pub fn uses(&self, feature: Feature) -> bool {
+        self.expr.uses(feature)
+    }
+}
+
+impl fmt::Display for ActiveDayExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    /// Byte offset of the next token
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, expected: &str) -> String {
+        match self.rest().chars().next() {
+            Some(_) => format!("{} at {:?}", expected, self.rest()),
+            None => format!("{}, but the expression ends", expected),
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.s.len() - self.rest().trim_start().len();
+    }
+
+    /// Skip this token if it's next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.compare()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.compare()?));
+        }
+        Ok(expr)
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let left = self.atom()?;
+        // The 2 character operators first, so `>=` isn't read as `>`
+        for (token, op) in [
+            ("==", CmpOp::Eq),
+            ("!=", CmpOp::Ne),
+            (">=", CmpOp::Ge),
+            ("<=", CmpOp::Le),
+            (">", CmpOp::Gt),
+            ("<", CmpOp::Lt),
+        ] {
+            if self.eat(token) {
+                return Ok(Expr::Cmp(Box::new(left), op, Box::new(self.atom()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.atom()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(expr);
+        }
+        self.skip_whitespace();
+        let word_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        let word = &self.rest()[..word_len];
+        let expr = if word.starts_with(|c: char| c.is_ascii_digit()) {
+            Expr::Num(
+                word.parse()
+                    .map_err(|_| format!("{:?} isn't a whole number", word))?,
+            )
+        } else if let Some((_, feature)) = Feature::ALL.iter().find(|(name, _)| *name == word) {
+            Expr::Feature(*feature)
+        } else if word.is_empty() {
+            return Err(self.error("expected a number, a feature, `!` or `(`"));
+        } else {
+            return Err(format!(
+                "there's no feature {:?}, only {}",
+                word,
+                Feature::ALL.map(|(name, _)| name).join(", ")
+            ));
+        };
+        self.pos += word_len;
+        Ok(expr)
+    }
+}
@@ -0,0 +1,123 @@
+//! `--countries`: which country (polygon) each node is in.
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// The polygons of each country, from a GeoJSON file.
+pub struct Countries {
+    countries: Vec<Country>,
+}
+
+struct Country {
+    /// min lon, min lat, max lon, max lat of all the polygons, to quickly skip most countries
+    bbox: [f64; 4],
+    /// Each polygon is a list of rings (the first is the outer one, the others holes), of
+    /// (lon, lat) points
+    polygons: Vec<Vec<Vec<(f64, f64)>>>,
+    /// The feature's `properties`
+    properties: Value,
+}
+
+impl Countries {
+    /// Read a GeoJSON `FeatureCollection`, with one `Polygon` or `MultiPolygon` feature per
+    /// country. Other geometries are ignored.
+    pub fn from_geojson(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Can't read countries file {}", path.display()))?;
+        let geojson: Value = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Invalid GeoJSON in {}", path.display()))?;
+        let features = geojson["features"]
+            .as_array()
+            .with_context(|| format!("{} isn't a GeoJSON FeatureCollection", path.display()))?;
+        let mut countries = Vec::new();
+        for feature in features {
+            let geometry = &feature["geometry"];
+            let polygons = match geometry["type"].as_str() {
+                Some("Polygon") => vec![polygon(&geometry["coordinates"])?],
+                Some("MultiPolygon") => geometry["coordinates"]
+                    .as_array()
+                    .context("Invalid MultiPolygon coordinates")?
+                    .iter()
+                    .map(polygon)
+                    .collect::<Result<_>>()?,
+                _ => continue,
+            };
+            let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+            for (lon, lat) in polygons.iter().flat_map(|rings| &rings[0]) {
+                bbox = [
+                    bbox[0].min(*lon),
+                    bbox[1].min(*lat),
+                    bbox[2].max(*lon),
+                    bbox[3].max(*lat),
+                ];
+            }
+            countries.push(Country {
+                bbox,
+                polygons,
+                properties: feature["properties"].clone(),
+            });
+        }
+        Ok(Countries { countries })
+    }
+
+    /// The number of countries, which `find` returns the index of.
+    pub fn len(&self) -> u16 {
+        self.countries.len() as u16
+    }
+
+    /// The `properties` of the feature of this country.
+    pub fn properties(&self, idx: u16) -> &Value {
+        &self.countries[usize::from(idx)].properties
+    }
+
+    /// The index of the (first) country this point is in.
+    pub fn find(&self, lon: f64, lat: f64) -> Option<u16> {
+        self.countries
+            .iter()
+            .position(|country| {
+                let [min_lon, min_lat, max_lon, max_lat] = country.bbox;
+                (min_lon..=max_lon).contains(&lon)
+                    && (min_lat..=max_lat).contains(&lat)
+                    && country
+                        .polygons
+                        .iter()
+                        .any(|rings| in_polygon(rings, lon, lat))
+            })
+            .map(|idx| idx as u16)
+    }
+}
+
+fn polygon(coordinates: &Value) -> Result<Vec<Vec<(f64, f64)>>> {
+    let rings = coordinates
+        .as_array()
+        .filter(|rings| !rings.is_empty())
+        .context("Invalid Polygon coordinates")?;
+    rings
+        .iter()
+        .map(|ring| {
+            ring.as_array()
+                .context("Invalid Polygon ring")?
+                .iter()
+                .map(|point| {
+                    Ok((
+                        point[0].as_f64().context("Invalid point")?,
+                        point[1].as_f64().context("Invalid point")?,
+                    ))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Even-odd ray casting over all the rings, so points in holes are outside.
+fn in_polygon(rings: &[Vec<(f64, f64)>], lon: f64, lat: f64) -> bool {
+    let mut inside = false;
+    for ring in rings {
+        for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            if (a.1 > lat) != (b.1 > lat) && lon < a.0 + (lat - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
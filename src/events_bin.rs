@@ -0,0 +1,123 @@
+//! `--emit-events-bin` & `--events-input`: Every day each user edited on, as a compact binary
+//! file, which can be read instead of the OSM file, to quickly recalculate the numbers with
+//! another window or threshold.
+//!
+//! The file starts with `MAGIC`, the format version (1 byte), and the first date, as the number
+//! of days since 1970-01-01 (4 bytes, little endian, signed). Then there's one event per day each
+//! user edited on, in date & uid order, as 2 unsigned LEB128 varints: the number of days since the
+//! previous event (or the first date), and the uid, minus the uid of the previous event if that's
+//! on the same day.
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The files start with this.
+const MAGIC: &[u8] = b"OSMNACEV";
+
+/// Version of the format. Files of other versions can't be read.
+const VERSION: u8 = 1;
+
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+}
+
+/// Write the users of every day to `path`. Like the CSV files, it's written to `<path>.tmp`,
+/// and moved into place when finished.
+pub fn write(path: &Path, day_edit_users: &BTreeMap<NaiveDate, HashSet<u32>>) -> Result<()> {
+    crate::write_atomically(path, |output| write_events(output, day_edit_users))
+}
+
+fn write_events(
+    output: &mut BufWriter<File>,
+    day_edit_users: &BTreeMap<NaiveDate, HashSet<u32>>,
+) -> Result<()> {
+    let first_date = day_edit_users.keys().next().copied().unwrap_or(epoch());
+    output.write_all(MAGIC)?;
+    output.write_all(&[VERSION])?;
+    output.write_all(&((first_date - epoch()).num_days() as i32).to_le_bytes())?;
+    let mut last_date = first_date;
+    for (date, uids) in day_edit_users.iter() {
+        let mut uids: Vec<u32> = uids.iter().copied().collect();
+        uids.sort_unstable();
+        let mut last_uid = 0;
+        for uid in uids {
+            write_uvarint(output, (*date - last_date).num_days() as u64)?;
+            write_uvarint(output, u64::from(uid - last_uid))?;
+            last_date = *date;
+            last_uid = uid;
+        }
+    }
+    Ok(())
+}
+
+/// Read the users of every day from a file written by `write`.
+pub fn read(path: &Path) -> Result<BTreeMap<NaiveDate, HashSet<u32>>> {
+    let mut data = Vec::new();
+    BufReader::new(
+        File::open(path).with_context(|| format!("Can't read events file {}", path.display()))?,
+    )
+    .read_to_end(&mut data)?;
+    let header_len = MAGIC.len() + 1 + 4;
+    if data.len() < header_len || !data.starts_with(MAGIC) {
+        anyhow::bail!("{} isn't an --emit-events-bin file", path.display());
+    }
+    let version = data[MAGIC.len()];
+    if version != VERSION {
+        anyhow::bail!(
+            "{} is version {} of the events format, but only version {} can be read",
+            path.display(),
+            version,
+            VERSION
+        );
+    }
+    let first_day = i32::from_le_bytes(data[MAGIC.len() + 1..header_len].try_into().unwrap());
+    let mut date = epoch()
+        .checked_add_signed(chrono::Duration::days(first_day.into()))
+        .with_context(|| format!("{}: invalid first date", path.display()))?;
+    let mut data = &data[header_len..];
+    let mut day_edit_users: BTreeMap<NaiveDate, HashSet<u32>> = BTreeMap::new();
+    let mut uid = 0;
+    while !data.is_empty() {
+        let days = uvarint(&mut data).with_context(|| format!("{} is cut off", path.display()))?;
+        let uid_delta =
+            uvarint(&mut data).with_context(|| format!("{} is cut off", path.display()))?;
+        if days > 0 {
+            date = date
+                .checked_add_days(chrono::Days::new(days))
+                .with_context(|| format!("{}: invalid date", path.display()))?;
+            uid = 0;
+        }
+        uid = u32::try_from(u64::from(uid) + uid_delta)
+            .with_context(|| format!("{}: invalid uid", path.display()))?;
+        day_edit_users.entry(date).or_default().insert(uid);
+    }
+    Ok(day_edit_users)
+}
+
+fn write_uvarint(output: &mut impl Write, mut value: u64) -> Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.write_all(&[byte])?;
+            return Ok(());
+        }
+        output.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn uvarint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first().context("number is cut off")?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("number is too long")
+}
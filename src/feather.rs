@@ -0,0 +1,220 @@
+//! `--format arrow`: write `users_per_day` as an Arrow IPC (Feather v2) file.
+use crate::records::UserDay;
+use anyhow::Result;
+use arrow_array::builder::{
+    BooleanBuilder, Date32Builder, Float64Builder, Int64Builder, StringBuilder, UInt32Builder,
+    UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+/// Rows are collected, and written as one record batch, this many at a time.
+const BATCH_SIZE: usize = 65_536;
+
+/// Which of the optional `UserDay` columns there are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionalColumns {
+    pub edit_day_bitmap: bool,
+    pub countries_edited: bool,
+    pub edit_timestamps: bool,
+    pub restorations: bool,
+    pub objects_touched: bool,
+    pub distinct_tag_keys: bool,
+}
+
+/// An Arrow file of `UserDay` rows. Like `OutputCsv`, it's written to `<filename>.tmp`, and
+/// moved into place when finished.
+pub struct ArrowUserDays {
+    filename: String,
+    schema: Arc<Schema>,
+    writer: FileWriter<BufWriter<File>>,
+    num_rows: usize,
+    date: Date32Builder,
+    uid: UInt32Builder,
+    num_edit_days_last_yr: UInt64Builder,
+    username: StringBuilder,
+    ge42days: BooleanBuilder,
+    mapped_days: StringBuilder,
+    likely_mechanical: BooleanBuilder,
+    account_age_days: Int64Builder,
+    density: Float64Builder,
+    pattern: StringBuilder,
+    /// Only with `--emit-bitmap`
+    edit_day_bitmap: Option<StringBuilder>,
+    /// Only with `--countries`
+    countries_edited: Option<UInt64Builder>,
+    /// Only with `--precise-timestamps`
+    edit_timestamps: Option<(StringBuilder, StringBuilder)>,
+    /// Only with `--track-restorations`
+    restorations: Option<UInt64Builder>,
+    /// Only with `--track-objects`
+    objects_touched: Option<UInt64Builder>,
+    /// Only with `--track-tag-keys`
+    distinct_tag_keys: Option<UInt64Builder>,
+}
+
+impl ArrowUserDays {
+    /// `ge_days_column` is the name of the `ge42days` column.
+    pub fn create(
+        filename: String,
+        ge_days_column: &str,
+        optional_columns: OptionalColumns,
+    ) -> Result<Self> {
+        let OptionalColumns {
+            edit_day_bitmap,
+            countries_edited,
+            edit_timestamps,
+            restorations,
+            objects_touched,
+            distinct_tag_keys,
+        } = optional_columns;
+        let mut fields = vec![
+            Field::new("date", DataType::Date32, false),
+            Field::new("uid", DataType::UInt32, false),
+            Field::new("num_edit_days_last_yr", DataType::UInt64, false),
+            Field::new("username", DataType::Utf8, false),
+            Field::new(ge_days_column, DataType::Boolean, false),
+            Field::new("mapped_days", DataType::Utf8, false),
+            Field::new("likely_mechanical", DataType::Boolean, false),
+            Field::new("account_age_days", DataType::Int64, false),
+            Field::new("density", DataType::Float64, false),
+            Field::new("pattern", DataType::Utf8, false),
+        ];
+        if edit_day_bitmap {
+            fields.push(Field::new("edit_day_bitmap", DataType::Utf8, false));
+        }
+        if countries_edited {
+            fields.push(Field::new("countries_edited", DataType::UInt64, false));
+        }
+        if edit_timestamps {
+            fields.push(Field::new("first_edit_ts", DataType::Utf8, false));
+            fields.push(Field::new("last_edit_ts", DataType::Utf8, false));
+        }
+        if restorations {
+            fields.push(Field::new("restorations", DataType::UInt64, false));
+        }
+        if objects_touched {
+            fields.push(Field::new("objects_touched", DataType::UInt64, false));
+        }
+        if distinct_tag_keys {
+            fields.push(Field::new("distinct_tag_keys", DataType::UInt64, false));
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let file = BufWriter::new(File::create(format!("{}.tmp", filename))?);
+        let writer = FileWriter::try_new(file, &schema)?;
+        Ok(ArrowUserDays {
+            filename,
+            schema,
+            writer,
+            num_rows: 0,
+            date: Date32Builder::new(),
+            uid: UInt32Builder::new(),
+            num_edit_days_last_yr: UInt64Builder::new(),
+            username: StringBuilder::new(),
+            ge42days: BooleanBuilder::new(),
+            mapped_days: StringBuilder::new(),
+            likely_mechanical: BooleanBuilder::new(),
+            account_age_days: Int64Builder::new(),
+            density: Float64Builder::new(),
+            pattern: StringBuilder::new(),
+            edit_day_bitmap: edit_day_bitmap.then(StringBuilder::new),
+            countries_edited: countries_edited.then(UInt64Builder::new),
+            edit_timestamps: edit_timestamps.then(|| (StringBuilder::new(), StringBuilder::new())),
+            restorations: restorations.then(UInt64Builder::new),
+            objects_touched: objects_touched.then(UInt64Builder::new),
+            distinct_tag_keys: distinct_tag_keys.then(UInt64Builder::new),
+        })
+    }
+
+    pub fn serialize(&mut self, row: UserDay) -> Result<()> {
+        let epoch = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        self.date.append_value((row.date - epoch).num_days() as i32);
+        self.uid.append_value(row.uid);
+        self.num_edit_days_last_yr
+            .append_value(row.num_edit_days_last_yr as u64);
+        self.username.append_value(row.username);
+        self.ge42days.append_value(row.ge42days);
+        self.mapped_days.append_value(&row.mapped_days);
+        self.likely_mechanical.append_value(row.likely_mechanical);
+        self.account_age_days.append_value(row.account_age_days);
+        self.density.append_value(row.density);
+        self.pattern.append_value(row.pattern);
+        if let Some(builder) = self.edit_day_bitmap.as_mut() {
+            builder.append_value(row.edit_day_bitmap.unwrap_or_default());
+        }
+        if let Some(builder) = self.countries_edited.as_mut() {
+            builder.append_value(row.countries_edited.unwrap_or_default() as u64);
+        }
+        if let Some((first, last)) = self.edit_timestamps.as_mut() {
+            first.append_value(row.first_edit_ts.unwrap_or_default());
+            last.append_value(row.last_edit_ts.unwrap_or_default());
+        }
+        if let Some(builder) = self.restorations.as_mut() {
+            builder.append_value(row.restorations.unwrap_or_default());
+        }
+        if let Some(builder) = self.objects_touched.as_mut() {
+            builder.append_value(row.objects_touched.unwrap_or_default() as u64);
+        }
+        if let Some(builder) = self.distinct_tag_keys.as_mut() {
+            builder.append_value(row.distinct_tag_keys.unwrap_or_default() as u64);
+        }
+        self.num_rows += 1;
+        if self.num_rows >= BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Write all the collected rows.
+    fn write_batch(&mut self) -> Result<()> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.date.finish()),
+            Arc::new(self.uid.finish()),
+            Arc::new(self.num_edit_days_last_yr.finish()),
+            Arc::new(self.username.finish()),
+            Arc::new(self.ge42days.finish()),
+            Arc::new(self.mapped_days.finish()),
+            Arc::new(self.likely_mechanical.finish()),
+            Arc::new(self.account_age_days.finish()),
+            Arc::new(self.density.finish()),
+            Arc::new(self.pattern.finish()),
+        ];
+        if let Some(builder) = self.edit_day_bitmap.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        if let Some(builder) = self.countries_edited.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        if let Some((first, last)) = self.edit_timestamps.as_mut() {
+            columns.push(Arc::new(first.finish()));
+            columns.push(Arc::new(last.finish()));
+        }
+        if let Some(builder) = self.restorations.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        if let Some(builder) = self.objects_touched.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        if let Some(builder) = self.distinct_tag_keys.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        self.num_rows = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.num_rows > 0 {
+            self.write_batch()?;
+        }
+        self.writer.finish()?;
+        self.writer.get_mut().flush()?;
+        std::fs::rename(format!("{}.tmp", self.filename), &self.filename)?;
+        Ok(())
+    }
+}
@@ -1,221 +1,5438 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use base64::Engine;
 use chrono::naive::NaiveDate;
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
+use chrono::Datelike;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use osmio::prelude::*;
-use osmio::OSMObjBase;
+use osmio::ObjId;
 use rayon::prelude::*;
+use regex::Regex;
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
 
-#[derive(Parser, Debug)]
-#[command(version, about)]
+mod active_day;
+mod countries;
+mod events_bin;
+mod feather;
+mod o5m;
+mod parts;
+mod pbf_header;
+mod records;
+mod timezones;
+mod window;
+use active_day::{ActiveDayExpr, DayFeatures, Feature};
+use countries::Countries;
+use feather::{ArrowUserDays, OptionalColumns};
+use records::*;
+use timezones::TimeZones;
+use window::{window_user_changesets, window_user_days, window_user_edits, window_users};
+
+#[derive(Parser, Debug, serde::Serialize)]
+#[command(version, about, args_override_self = true)]
 struct Args {
-    /// OSM History file to read.
-    #[arg(short, long)]
-    input_filename: PathBuf,
+    /// Read arguments from this JSON file, an object whose keys are the long argument names
+    /// (e.g. `"min_edit_days": 10`, `"input_filename": ["a.osh.pbf", "b.osh.pbf"]`). Arguments
+    /// on the command line override the file's.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// OSM History file to read, as PBF or o5m. Can be given more than once, to read several files
+    /// (e.g. per-country extracts), which are merged. A file split into parts (e.g.
+    /// `planet.osh.pbf.part0`, `planet.osh.pbf.part1`, …) can be read as one, with a `*` at the
+    /// end, like `planet.osh.pbf.part*` (quoted, so the shell doesn't expand it).
+    #[arg(short, long, required_unless_present_any = ["input_list", "events_input"])]
+    input_filename: Vec<PathBuf>,
+
+    /// Also read every file listed in this file, one path per line. Blank lines & lines starting
+    /// with `#` are ignored.
+    #[arg(long)]
+    input_list: Option<PathBuf>,
 
-    /// All output files will be prefixed with this string.
+    /// All output files will be prefixed with this string. `strftime` tokens (e.g.
+    /// `reports/%Y-%m-%d_`) are replaced with the current local time. Use `%%` for a literal `%`.
+    /// The directory (e.g. `reports/`) is created if needed. An existing directory is used as
+    /// a directory, even without a `/` at the end.
     #[arg(short = 'p', long, default_value = "")]
     output_prefix: String,
 
-    /// Output only includes entries for people who have mapped at least this many days. default: 0
+    /// Output only includes entries for people who have mapped at least this many days in the
+    /// rolling window (see `--min-total-edit-days` for all their days).
     ///
     /// Often a large number of mappers have 1 or 2 edit days, which clutters the data.
     #[arg(long, default_value = "20")]
     min_edit_days: u32,
 
+    /// Output only includes entries for people who have mapped at most this many days in the
+    /// rolling window. Together with `--min-edit-days` this selects a band of mappers, e.g. casual
+    /// mappers with 5–20 days.
+    #[arg(long)]
+    max_edit_days: Option<u32>,
+
+    /// Output only includes entries for people who have mapped at least this many days in total,
+    /// from their first edit up to the date (not only in the rolling window), e.g. to only
+    /// include veterans.
+    #[arg(long, default_value = "0")]
+    min_total_edit_days: u32,
+
+    /// Output only includes the N people who have mapped the most days in the rolling window, on
+    /// each date. Ties are broken by lowest uid.
+    #[arg(long, value_name = "N")]
+    top_n_per_day: Option<usize>,
+
     /// When producing per-day stats, start on this first day. Default is to start from the
     /// earliest day in the history file.
     #[arg(long)]
     start_date: Option<NaiveDate>,
 
-    /// When producing per-day stats, produce stats up to this date. Default is today.
+    /// When producing per-day stats, produce stats up to this date. Default is today. This date
+    /// is included too, unless `--end-exclusive`.
     #[arg(long)]
     end_date: Option<NaiveDate>,
 
-    /// When producing per-day stats, include at least this many days in the output.
+    /// Include the `--end-date` in the per-day stats. This is the default.
+    #[arg(long, overrides_with = "end_exclusive")]
+    end_inclusive: bool,
+
+    /// Stop the per-day stats the day before `--end-date`, so that it's a half-open range, like
+    /// `--start-date 2023-01-01 --end-date 2024-01-01` for all of 2023.
+    #[arg(long, overrides_with = "end_inclusive")]
+    end_exclusive: bool,
+
+    /// Only write the per-day stats for the dates in this file (e.g. the first of every month),
+    /// one `YYYY-MM-DD` date per line, which is much faster than every day. Blank lines & lines
+    /// starting with `#` are ignored.
+    #[arg(long, value_name = "PATH")]
+    dates_file: Option<PathBuf>,
+
+    /// Skip all objects from before this date while reading, which is faster than reading the
+    /// whole history. The rolling windows of the days up to 1 window after this date will be
+    /// incomplete, and first edits (e.g. for `account_age_days`) are the first since this date.
+    #[arg(long, value_name = "DATE")]
+    read_from: Option<NaiveDate>,
+
+    /// When producing per-day stats, include at least this many days in the output. If the
+    /// `--start-date`–`--end-date` range is shorter, the start date is moved earlier (but never
+    /// before the first day in the file).
     #[arg(long, default_value = "3")]
-    min_num_days: Option<u32>,
+    min_num_days: u32,
+
+    /// Only write the first N days of `user_totals_per_day.csv` & `users_per_day.csv` (or
+    /// `metrics_long.csv`), for a quick preview. The whole input is still read.
+    #[arg(long, alias = "head", value_name = "N")]
+    limit_days: Option<usize>,
+
+    /// Also write `net_flow.csv`, with the number of new, returning & churned contributors per
+    /// period.
+    #[arg(long)]
+    net_flow: bool,
+
+    /// Length of each period in `net_flow.csv`.
+    #[arg(long, value_enum, default_value_t = Period::Week)]
+    net_flow_period: Period,
+
+    /// Also write `resampled.csv`, the number of users, and the `rolling_yr_total` at the end,
+    /// of every day, week or month, including ones when no one edited, for a gapless time series.
+    #[arg(long, value_enum, value_name = "FREQ")]
+    resample: Option<Period>,
+
+    /// Only count objects whose version is at least this. e.g. `2` ignores object creations, and
+    /// only counts edits to existing data.
+    #[arg(long)]
+    min_object_version: Option<u32>,
+
+    /// Only count objects with at least one tag. This skips untagged nodes (e.g. the nodes of
+    /// ways), and deletions.
+    #[arg(long)]
+    require_tags: bool,
+
+    /// Don't count edits made on Saturdays & Sundays (UTC, or local with
+    /// `--timezone-per-region`), e.g. to study mapping during working hours.
+    #[arg(long)]
+    exclude_weekends: bool,
+
+    /// Ignore every edit made between these dates (inclusive), like `2024-03-01..2024-03-15`,
+    /// e.g. a known import. Can be given more than once.
+    #[arg(long, value_name = "START..END", value_parser = parse_date_range)]
+    exclude_range: Vec<std::ops::RangeInclusive<NaiveDate>>,
+
+    /// Only count the users whose uid is M modulo N, like `0/4`, to split the work over N
+    /// machines. Every user's edits are all in one shard, so the `--emit-events-bin` files of
+    /// all the shards can be read together with `--events-input`.
+    #[arg(long, value_name = "M/N", value_parser = parse_uid_shard)]
+    uid_shard: Option<(u32, u32)>,
+
+    /// Skip edits with a timestamp after the replication timestamp of the PBF file (when it's
+    /// "data current as of"), which must be from a wrong clock, rather than letting them
+    /// extend the date range.
+    #[arg(long)]
+    ignore_future_edits: bool,
+
+    /// Count the objects with a timestamp before `--clock-skew-before`, or after the
+    /// replication timestamp of the PBF file (or now, if there's none), which must be from a
+    /// wrong clock, and print how many there are, with some examples. They're still counted.
+    #[arg(long)]
+    warn_on_clock_skew: bool,
+
+    /// With `--warn-on-clock-skew`, timestamps before this date are wrong. The default is
+    /// when OpenStreetMap started, but there are only a few edits from before 2005.
+    #[arg(long, value_name = "DATE", default_value = "2004-08-09")]
+    clock_skew_before: NaiveDate,
+
+    /// Also write `editor_usage.csv`, with the number of users per editor software per day,
+    /// based on the `created_by` tag of objects. Objects without that tag are not included.
+    #[arg(long)]
+    by_editor: bool,
+
+    /// A user is flagged as `likely_mechanical` if they average more than this many edits per
+    /// changeset…
+    #[arg(long, default_value = "1000")]
+    mechanical_edits_per_changeset: u64,
+
+    /// …and more than this many edits per edit day.
+    #[arg(long, default_value = "2000")]
+    mechanical_edits_per_day: u64,
+
+    /// The `pattern` of a user in `users_per_day.csv` is `burst` if all their edit days in the
+    /// window are within this many days…
+    #[arg(long, default_value = "7", value_name = "DAYS")]
+    burst_max_span_days: i64,
+
+    /// …otherwise `daily` if their `density` is at least this…
+    #[arg(long, default_value = "0.5")]
+    daily_min_density: f64,
+
+    /// …otherwise `weekly` if there are never more than this many days from one of their edit
+    /// days to the next, and `sporadic` if there are.
+    #[arg(long, default_value = "7", value_name = "DAYS")]
+    weekly_max_gap_days: i64,
+
+    /// Only print the date range & number of objects in the input file, and exit. No CSV files
+    /// are written.
+    #[arg(long)]
+    range_only: bool,
+
+    /// Template for the progress bar, in `indicatif`'s `ProgressStyle` format.
+    #[arg(long, default_value = DEFAULT_PROGRESS_TEMPLATE)]
+    progress_template: String,
+
+    /// Redraw the progress bar at most once every this many milliseconds.
+    #[arg(long, default_value = "50")]
+    progress_refresh: u64,
+
+    /// Also write `type_activity.csv`, with the number of nodes, ways & relations edited per day.
+    #[arg(long)]
+    type_activity: bool,
+
+    /// Take the anonymous edits (uid 0) out of all the other files, so that they're only about
+    /// registered accounts, and write `anonymous_per_day.csv`, with the number of anonymous &
+    /// registered edits per day.
+    #[arg(long)]
+    split_anonymous: bool,
+
+    /// Only write `acquisition.csv`, the number of users whose first ever edit was on each day.
+    /// The (slow) rolling window files `user_totals_per_day.csv` & `users_per_day.csv` are not
+    /// written.
+    #[arg(long)]
+    first_edit_only: bool,
+
+    /// Leave out the rows of `user_totals_per_day.csv` (or `metrics_long.csv`) for days when no
+    /// one edited, and the `rolling_yr_total` didn't change, to make the files of regions with
+    /// little activity much shorter.
+    #[arg(long)]
+    skip_empty_days: bool,
+
+    /// If `user_totals_per_day.csv` & `users_per_day.csv` (or `metrics_long.csv`) already exist,
+    /// add the days after the last day in them, instead of rewriting them, e.g. for daily updates
+    /// with `--read-from`. The files must have the same columns.
+    #[arg(long)]
+    append: bool,
+
+    /// Print the "record days" at the end: the day with the most users, and the day with the
+    /// highest `rolling_yr_total`.
+    #[arg(long)]
+    summary: bool,
+
+    /// Print the number of active, new & churned users in the rolling windows ending on these 2
+    /// dates side by side, and the change, e.g. to compare this year with last year.
+    #[arg(long, num_args = 2, value_names = ["A_END", "B_END"])]
+    compare_windows: Vec<NaiveDate>,
+
+    /// Count objects without a uid (e.g. redacted ones) as anonymous edits, instead of skipping
+    /// them. Objects without a timestamp are always skipped.
+    #[arg(long)]
+    include_redactions: bool,
+
+    /// Add `first_edit_ts` & `last_edit_ts` columns to `users_per_day.csv`, the exact times of
+    /// each user's first & last edit in the input.
+    #[arg(long)]
+    precise_timestamps: bool,
+
+    /// Print the retention rate: the fraction of users active in a month who are also active
+    /// this many months later, averaged over all months.
+    #[arg(long, value_name = "N")]
+    retention_n_months: Option<u32>,
+
+    /// Also write `survival.csv`, the fraction of all users who are still active each number of
+    /// months after their first edit, a Kaplan-Meier survival curve.
+    #[arg(long)]
+    survival: bool,
+
+    /// Also write `newcomer_activity.csv`, the fraction of users who edited on each of the first
+    /// DAYS days since their first edit, with every user's first edit as day 0, i.e. how quickly
+    /// newcomers' activity decays.
+    #[arg(long, value_name = "DAYS")]
+    since_first_edit_window: Option<usize>,
+
+    /// Print the autocorrelation of the number of users per day, 7 & 365 days apart, to show
+    /// how weekly & yearly the activity is.
+    #[arg(long)]
+    autocorrelation: bool,
+
+    /// Print the contributor funnel: how many users edited at all, and how many of them have at
+    /// least 7, 30 & 365 days between their first & last edit.
+    #[arg(long)]
+    funnel: bool,
+
+    /// Check that the rows of `user_totals_per_day.csv` are consistent (e.g. `rolling_yr_total`
+    /// is never less than `num_users`), print every violation, and fail if there are any. This
+    /// is for catching bugs in this tool.
+    #[arg(long)]
+    validate: bool,
+
+    /// Fast mode: only write `user_totals_per_day.csv`, with just the `date` & `num_users`
+    /// columns. None of the (slow) rolling windows are calculated, and `users_per_day.csv` isn't
+    /// written.
+    #[arg(long, conflicts_with_all = ["experience_bands", "stacked_cohorts", "bus_factor", "inequality_metrics", "normalize", "sticky_threshold", "creators", "baseline_date", "validate", "flag_outlier_days", "type_mix", "grafana"])]
+    no_rolling: bool,
+
+    /// Add a `countries_edited` column to `users_per_day.csv`, the number of countries each
+    /// user has edited nodes in. The countries are the `Polygon` & `MultiPolygon` features of
+    /// this GeoJSON file.
+    #[arg(long, value_name = "GEOJSON")]
+    countries: Option<PathBuf>,
+
+    /// The regions for `--region-timeseries`, the `Polygon` & `MultiPolygon` features of this
+    /// GeoJSON file, named by their `name` property.
+    #[arg(long, value_name = "GEOJSON", requires = "region_timeseries")]
+    regions: Option<PathBuf>,
+
+    /// Also write `region_timeseries.csv`, the number of users who edited nodes in each of the
+    /// `--regions`, on each day & in the rolling window. This keeps the users of every day for
+    /// every region.
+    #[arg(long, requires = "regions")]
+    region_timeseries: bool,
+
+    /// Add an `active_per_100k` column to `region_timeseries.csv`, the `rolling_yr_total` per
+    /// 100,000 inhabitants, from this CSV file with `region` & `population` columns, to compare
+    /// regions of different sizes.
+    #[arg(long, value_name = "CSV", requires = "region_timeseries")]
+    population: Option<PathBuf>,
+
+    /// Usernames longer than this many characters (which should only be in malformed data) are
+    /// cut off, and end with `…`.
+    #[arg(long, default_value = "255")]
+    max_username_len: usize,
+
+    /// Only count these objects. File with one object per line, as `node/123`, `way/456` or
+    /// `relation/789`. Blank lines & lines starting with `#` are ignored.
+    #[arg(long)]
+    object_ids: Option<PathBuf>,
+
+    /// Also write `year_overlap.csv`, comparing the users active in each calendar year with the
+    /// previous year.
+    #[arg(long)]
+    year_overlap: bool,
+
+    /// Also write `cohort_quality.csv`, how many of the users whose first edit was in each month
+    /// edited on more than one day.
+    #[arg(long)]
+    cohort_quality: bool,
+
+    /// Length of the rolling window, for `rolling_yr_total`, `num_edit_days_last_yr` etc. A
+    /// number of days (`365`/`365d`), weeks (`52w`), months (`12m`, a month is 1/12th of 365
+    /// days), or years (`1y`), up to 1,000,000 days.
+    #[arg(long, alias = "window", default_value = "365d", value_parser = parse_window_days)]
+    window_days: u64,
+
+    /// Use the window & threshold of a well known definition of "active", for numbers which can
+    /// be compared with others. Arguments which are given override the preset's.
+    #[arg(long, value_enum)]
+    preset: Option<Preset>,
+
+    /// How the rolling window is defined for each user in `users_per_day.csv`. With
+    /// `active-days`, the users listed for a date are still the ones who edited in the
+    /// `--window-days` days up to it, but each user's columns are based on their last
+    /// `--window-days` edit days up to that date, however long ago those were. With `centered`,
+    /// the window is the `--window-days` days around each date, half before & half after, in
+    /// both files. The windows of the dates within half a window of the start or end of the
+    /// input are incomplete, so those numbers are meaningless. Users who only start editing
+    /// later in the window have a negative `account_age_days`.
+    #[arg(long, value_enum, default_value_t = WindowMode::Calendar)]
+    window_mode: WindowMode,
+
+    /// Print how long reading the input, and writing each output file took, to stderr.
+    #[arg(long)]
+    timing: bool,
+
+    /// Print the peak memory use after reading the input (only on Linux).
+    #[arg(long)]
+    profile_memory: bool,
+
+    /// Exclude users whose username matches this regular expression (`regex` crate syntax, e.g.
+    /// `(?i)bot$|_import$`). Users are excluded if the username on an object matches, or if
+    /// their last seen username matches.
+    #[arg(long)]
+    #[serde(serialize_with = "serialize_optional_display")]
+    exclude_bots_by_name: Option<Regex>,
+
+    /// Count the edits of some accounts as another one, e.g. the alternate accounts of one
+    /// mapper. One `old_uid=canonical_uid` per line. Blank lines & lines starting with `#` are
+    /// ignored.
+    #[arg(long, value_name = "PATH")]
+    merge_uids: Option<PathBuf>,
+
+    /// Also write `hour_histogram.csv`, the total number of edits per hour of the day.
+    #[arg(long)]
+    hour_histogram: bool,
+
+    /// Also write `geohash_counts.csv`, the number of users & node edits in each geohash cell
+    /// of this many characters (1 to 12, 4 is about 40 km), as a quick map of the activity.
+    #[arg(long, value_name = "PRECISION", value_parser = clap::value_parser!(u8).range(1..=12))]
+    geohash_grid: Option<u8>,
+
+    /// Time zone for `--hour-histogram`, as a UTC offset, e.g. `+02:00`. Default is UTC.
+    #[arg(long, default_value = "+00:00")]
+    #[serde(serialize_with = "serialize_display")]
+    timezone: chrono::FixedOffset,
+
+    /// Count each node edit on the day in the local time zone where the node is, rather than
+    /// the UTC day. The time zones are the `Polygon` & `MultiPolygon` features of this GeoJSON
+    /// file, each with a `zone` property, the UTC offset in hours, like Natural Earth's
+    /// `ne_10m_time_zones`. Ways, relations, deleted nodes, and nodes outside all the time zones
+    /// are counted on the UTC day.
+    #[arg(long, value_name = "GEOJSON")]
+    timezone_per_region: Option<PathBuf>,
+
+    /// Format of the dates, in `strftime` syntax, e.g. `%d.%m.%Y`. It's used for all the date
+    /// columns of the CSV files, and the days in the `mapped_days` column.
+    #[arg(long, default_value = "%Y-%m-%d", value_parser = parse_date_format)]
+    date_format: String,
+
+    /// Write the uids in the CSV files as Excel text formulas, like `="123"`, so Excel doesn't
+    /// treat them as numbers, and change them. Usernames which look like numbers or formulas
+    /// are written like that too. Other programs will read the `="…"`.
+    #[arg(long)]
+    excel_safe: bool,
+
+    /// Only include users in `users_per_day.csv` whose first ever edit is within the output date
+    /// range (`--start-date` to `--end-date`), i.e. only new mappers.
+    #[arg(long)]
+    only_new_in_range: bool,
+
+    /// Also write `experience_bands.csv`, splitting the users active in the rolling window on
+    /// each day by how long ago their first edit was.
+    #[arg(long)]
+    experience_bands: bool,
+
+    /// Also write `stacked_cohorts.csv`, splitting the users active in the rolling window on each
+    /// day by the year of their first edit.
+    #[arg(long)]
+    stacked_cohorts: bool,
+
+    /// Also write `window_distribution.csv`, the number of users per number of edit days in the
+    /// rolling window, at the end of every month (and the last day in the file).
+    #[arg(long)]
+    window_distribution: bool,
+
+    /// Also write `tier_transitions.csv`, the number of casual (fewer than 10 edit days in the
+    /// rolling window), regular & core (at least `--active-threshold`) users at the end of every
+    /// month, and how many moved to a higher or lower tier since the month before.
+    #[arg(long)]
+    tier_transitions: bool,
+
+    /// Write `users_per_day.csv` as one file per year, `users_per_day.<year>.csv`.
+    #[arg(long)]
+    partition_by_year: bool,
+
+    /// Count a user's edits as the number of distinct objects they edited, rather than the number
+    /// of object versions they made. (Used for `likely_mechanical`)
+    #[arg(long)]
+    count_distinct_objects: bool,
+
+    /// Count all the versions a user made of one object on one day as one edit, rather than
+    /// counting every version, so that saving an object many times in one session doesn't
+    /// inflate the number of edits. This keeps every object every user edited on every day.
+    #[arg(long, conflicts_with = "count_distinct_objects")]
+    dedupe_same_day_object: bool,
+
+    /// A user is "active" on a date if they've edited on at least this many days (or made this
+    /// many edits, with `--active-by edits`) in the rolling window. The `ge42days` &
+    /// `users_ge42_days` column names use this number.
+    #[arg(long, default_value = "42")]
+    active_threshold: usize,
+
+    /// Like `--active-threshold`, but as a fraction of `--window-days`, e.g. `0.1` is active on
+    /// at least 10% of the days in the window (rounded up).
+    #[arg(long, conflicts_with = "active_threshold", value_name = "FRACTION")]
+    active_fraction: Option<f64>,
+
+    /// Whether `--active-threshold` is a number of edit days, or of edits. With `edits`, the
+    /// columns are called `ge42edits` & `users_ge42_edits`. This keeps the number of edits of
+    /// every user on every day.
+    #[arg(long, value_enum, default_value_t = ActiveBy::Days)]
+    active_by: ActiveBy,
+
+    /// How to write the output. `tidy` writes `metrics_long.csv`, with one row per date & metric,
+    /// instead of `user_totals_per_day.csv`. `arrow` writes `users_per_day.arrow`, an Arrow IPC
+    /// (Feather) file, instead of `users_per_day.csv`.
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Only count the edits each user made in the first N days from their first ever edit (the
+    /// day of the first edit is day 1), for studying newcomers. This needs an extra pass over the
+    /// input, to find everyone's first edit.
+    #[arg(long, value_name = "N")]
+    first_n_days: Option<u64>,
+
+    /// Only count edits of nodes at or north of this latitude, and of the ways & relations with
+    /// such a node. This needs an extra pass over the input, to find the nodes.
+    #[arg(long, allow_negative_numbers = true, value_name = "LAT")]
+    min_lat: Option<f64>,
+
+    /// Like `--min-lat`, but only at or south of this latitude.
+    #[arg(long, allow_negative_numbers = true, value_name = "LAT")]
+    max_lat: Option<f64>,
+
+    /// Add an `edit_day_bitmap` column to `users_per_day.csv`, a compact base64 encoded bitset
+    /// of the days in the rolling window this user edited on.
+    #[arg(long)]
+    emit_bitmap: bool,
+
+    /// Compare the new `user_totals_per_day.csv` with this older one (e.g. from before a code
+    /// change), and print every date & column where they differ.
+    #[arg(long, value_name = "OLD_CSV")]
+    compare: Option<PathBuf>,
+
+    /// Also write a profile of every user to this file, as JSON lines: their first & last edit,
+    /// edit days, longest streak & gap, number of edits & changesets, and the bounding box of
+    /// their node edits.
+    #[arg(long, value_name = "PATH")]
+    profiles: Option<PathBuf>,
+
+    /// Add `weighted_edits` to the `--profiles`, the edits of each user, weighted by how much
+    /// work they probably were, rather than all counting as 1. This doesn't change the
+    /// `--bus-factor` or `--inequality-metrics`, see `--weight` for those.
+    #[arg(long, requires = "profiles")]
+    weight_by: Option<EditWeight>,
+
+    /// Also write the daily numbers of users, new users, churned users (who left the window that
+    /// day) & the rolling total to this file, as the JSON which Grafana's JSON datasources
+    /// expect: a list of targets, each with `[value, timestamp_ms]` datapoints.
+    #[arg(long, value_name = "PATH")]
+    grafana: Option<PathBuf>,
+
+    /// Don't count any edits in changesets with more than this many objects, e.g. bulk imports.
+    /// This needs an extra pass over the input, to count the objects in each changeset.
+    #[arg(long, value_name = "N")]
+    exclude_changesets_over: Option<u64>,
+
+    /// Only count edits in changesets with at least this many objects, to leave out small
+    /// one-object tweaks. This needs an extra pass over the input, like
+    /// `--exclude-changesets-over`.
+    #[arg(long, value_name = "N")]
+    min_changeset_size: Option<u64>,
+
+    /// Only count a day as an edit day of a user if they made at least this many edits on it, to
+    /// leave out "drive-by" days with a single edit.
+    #[arg(long, value_name = "K")]
+    min_edits_per_day: Option<u64>,
+
+    /// Only count a day as an edit day of a user if this expression is true for what they did
+    /// that day, like `edit_count >= 3 && has_tags`. It can use `edit_count` (object versions),
+    /// `distinct_objects` & `has_tags` (whether any version has a tag), numbers, `==`, `!=`,
+    /// `>=`, `<=`, `>`, `<`, `!`, `&&`, `||` & brackets.
+    #[arg(long, value_name = "EXPR", value_parser = ActiveDayExpr::parse, conflicts_with = "events_input")]
+    #[serde(serialize_with = "serialize_optional_display")]
+    active_day_expr: Option<ActiveDayExpr>,
+
+    /// Don't count any edits in the changeset with the most objects. This needs an extra pass
+    /// over the input.
+    #[arg(long)]
+    exclude_largest_changeset: bool,
+
+    /// Also write `heatmap.csv`, the number of users active in each ISO week, with one row per
+    /// week of the year, and one column per year.
+    #[arg(long)]
+    heatmap: bool,
+
+    /// Also write `isoweek.csv`, the number of users, and new users, in each ISO week.
+    #[arg(long)]
+    isoweek: bool,
+
+    /// Also write `seasonality.csv`, the average number of users per day in each calendar month,
+    /// over all years.
+    #[arg(long)]
+    seasonality: bool,
+
+    /// Also write `co_edit_graph.csv`, an edge list of pairs of users who edited the same
+    /// objects. This needs a lot of memory, as every editor of every object is kept.
+    #[arg(long)]
+    co_edit_graph: bool,
+
+    /// Objects edited by more than this many users are ignored for `--co-edit-graph`. They're
+    /// usually large, widely edited objects (e.g. coastlines or country borders), which would add
+    /// an edge between almost everyone.
+    #[arg(long, default_value = "50")]
+    max_object_editors: usize,
+
+    /// Also write `user_<uid>.csv`, with details of every edit day of this one user.
+    #[arg(long, value_name = "UID")]
+    user: Option<u32>,
+
+    /// On the first Ctrl-C while reading the input, stop reading, and write the output from the
+    /// objects read so far, with `PARTIAL_` at the start of each filename. A second Ctrl-C exits
+    /// immediately.
+    #[arg(long)]
+    partial_on_interrupt: bool,
+
+    /// Stop reading after this many objects (in each pass over the input), for a quick test run
+    /// of the options on a big file. The results are only from those objects.
+    #[arg(long, value_name = "N")]
+    max_objects: Option<u64>,
+
+    /// Also write `inter_arrival.csv`, with the gaps (in days) between each user's consecutive
+    /// edit days.
+    #[arg(long)]
+    inter_arrival: bool,
+
+    /// Also write `reactivation.csv`, with every time a user edited again after not editing for
+    /// at least `--reactivation-gap-days` days.
+    #[arg(long)]
+    reactivation: bool,
+
+    /// The shortest gap (in days, between consecutive edit days) which is a reactivation.
+    #[arg(long, default_value = "90", value_name = "DAYS")]
+    reactivation_gap_days: i64,
+
+    /// Also write `edit_events.csv`, every day each user edited on, as `uid,date`, to calculate
+    /// other metrics with other tools.
+    #[arg(long)]
+    emit_events: bool,
+
+    /// Also write every day each user edited on, with their number of edits, to this file, as
+    /// JSON lines, in date order. It's flushed after every day, so a dashboard can read it while
+    /// it's written. With `--append`, only the days after the last one in the file are added.
+    #[arg(long, value_name = "PATH")]
+    emit_events_jsonl: Option<PathBuf>,
+
+    /// Also write every day each user edited on to this file, in a compact binary format, which
+    /// can be read again with `--events-input`.
+    #[arg(long, value_name = "PATH")]
+    emit_events_bin: Option<PathBuf>,
+
+    /// Read the edit days from this `--emit-events-bin` file, instead of an OSM file, which is
+    /// much faster, to recalculate the numbers with another window or threshold. There are no
+    /// usernames, edits, changesets or locations in it, so everything which needs them is empty.
+    /// Can be given more than once (e.g. for the `--uid-shard`s), and they're merged.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["input_filename", "input_list", "emit_events_jsonl", "user_calendars"]
+    )]
+    events_input: Vec<PathBuf>,
+
+    /// Also write a calendar of each user with at least `--min-edit-days` edit days in the last
+    /// window (up to `--end-date`), to this directory, as `<uid>.csv`: the number of edits on
+    /// every day of that window, e.g. to draw a contribution calendar.
+    #[arg(long, value_name = "DIR")]
+    user_calendars: Option<PathBuf>,
+
+    /// Add a `bus_factor` column to `user_totals_per_day.csv`, the fewest users who made more
+    /// than half of the edit days (or the `--weight`) in the window.
+    #[arg(long)]
+    bus_factor: bool,
+
+    /// Add `gini` & `theil` columns to `user_totals_per_day.csv`, the Gini coefficient & Theil
+    /// index of the number of edit days (or the `--weight`) of each user in the window.
+    #[arg(long)]
+    inequality_metrics: bool,
+
+    /// What the `--bus-factor`, and the Gini & Theil of `--inequality-metrics`, count for each
+    /// user. With `edits`, the number of edits of every user on every day is kept. (Not to be
+    /// confused with `--weight-by`, which is only for the `--profiles`.)
+    #[arg(long, value_enum, default_value_t = Weight::Days)]
+    weight: Weight,
+
+    /// Add a `users_per_1k_edits` column to `user_totals_per_day.csv`, the number of users in
+    /// the window per 1000 edits in the window, to compare regions of different sizes.
+    #[arg(long)]
+    normalize: bool,
+
+    /// Add a `sticky_new_users` column to `user_totals_per_day.csv`, the number of users whose
+    /// first ever edit was on that day, and who went on to edit on at least N more days.
+    #[arg(long, value_name = "N")]
+    sticky_threshold: Option<usize>,
+
+    /// Add `creators` & `maintainers` columns to `user_totals_per_day.csv`, the users in the
+    /// window who created at least one object (version 1), and the ones who only edited
+    /// existing objects.
+    #[arg(long)]
+    creators: bool,
+
+    /// Add `node_only_users`, `way_relation_only_users` & `mixed_type_users` columns to
+    /// `user_totals_per_day.csv`, the users in the window who only edited nodes, only ways &
+    /// relations, or both, as a sign of the mix of skills in the community.
+    #[arg(long)]
+    type_mix: bool,
+
+    /// Add a `rolling_yr_index` column to `user_totals_per_day.csv`, the `rolling_yr_total` as
+    /// a percentage of the `rolling_yr_total` on this date, to compare the growth of regions of
+    /// different sizes.
+    #[arg(long, value_name = "DATE")]
+    baseline_date: Option<NaiveDate>,
+
+    /// Add an `is_outlier` column to `user_totals_per_day.csv`, `yes` on days when `num_users`
+    /// is more than this many times the median of the 30 days before, e.g. to spot imports or
+    /// mapathons.
+    #[arg(long, value_name = "FACTOR")]
+    flag_outlier_days: Option<f64>,
+
+    /// Add a `restorations` column to `users_per_day.csv`, the number of edits in the window
+    /// which recreated a deleted object (e.g. reverting vandalism). This keeps every version of
+    /// every object.
+    #[arg(long)]
+    track_restorations: bool,
+
+    /// Add an `objects_touched` column to `users_per_day.csv`, the number of different objects
+    /// each user edited in the window. This keeps every object every user edited on every day.
+    #[arg(long)]
+    track_objects: bool,
+
+    /// Add a `distinct_tag_keys` column to `users_per_day.csv`, the number of different tag keys
+    /// on the objects each user edited in the window, to tell apart specialists from
+    /// generalists. This keeps every tag key every user edited on every day.
+    #[arg(long)]
+    track_tag_keys: bool,
+
+    /// With `--track-tag-keys`, keep at most this many tag keys per user per day, to save memory.
+    /// `distinct_tag_keys` is then at most this.
+    #[arg(long, value_name = "N", requires = "track_tag_keys")]
+    max_tag_keys: Option<usize>,
+
+    /// Stop with an error if a row can't be written to a CSV file. Otherwise the row is skipped
+    /// (with a warning), and the rest of the file is still written.
+    #[arg(long)]
+    strict: bool,
+
+    /// Also write `tag_changes.csv`, how many of each user's edits changed the tags, and how many
+    /// changed the geometry (the location, nodes or members), compared to the previous version.
+    /// This keeps a hash of the tags & geometry of every version of every object.
+    #[arg(long)]
+    track_tag_changes: bool,
+
+    /// Print a hash of the input files' sizes & modification times, all the arguments (except
+    /// `--output-prefix`), and the version of this tool. When it's unchanged, the output would
+    /// be the same.
+    #[arg(long)]
+    print_run_hash: bool,
+
+    /// Print all the arguments, after reading the `--config` file, with the defaults, and the
+    /// input files & output prefix resolved, as JSON, and exit without reading the input.
+    #[arg(long)]
+    explain: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let input_file = File::open(args.input_filename)?;
-    let input_bar = ProgressBar::new(input_file.metadata()?.len());
-    let mut reader = osmio::pbf::PBFReader::new(input_bar.wrap_read(input_file));
-    input_bar.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {eta} {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-        )
-        .unwrap(),
-    );
+/// For `Args` fields without a `Serialize` implementation.
+fn serialize_display<S: serde::Serializer>(
+    value: &impl std::fmt::Display,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
 
-    let (user_edit_days, day_edit_users, last_username): (
-        HashMap<u32, BTreeSet<NaiveDate>>,
-        BTreeMap<NaiveDate, HashSet<u32>>,
-        HashMap<u32, (i64, String)>,
-    ) = reader
-        .objects()
-        .par_bridge()
-        .fold(
-            Default::default,
-            |(mut user_edit_days, mut day_edit_users, mut last_username): (
-                HashMap<u32, BTreeSet<NaiveDate>>,
-                BTreeMap<NaiveDate, HashSet<u32>>,
-                HashMap<u32, (i64, String)>,
-            ),
-             o| {
-                let timestamp = o.timestamp().as_ref().unwrap().to_epoch_number();
-                let day_string = o.timestamp().as_ref().unwrap().to_iso_string();
-                let day = NaiveDate::from_ymd_opt(
-                    day_string.get(0..4).unwrap().parse().unwrap(),
-                    day_string.get(5..7).unwrap().parse().unwrap(),
-                    day_string.get(8..10).unwrap().parse().unwrap(),
-                )
-                .unwrap();
-                let uid = o.uid().unwrap();
-                if last_username
-                    .get(&uid)
-                    .map_or(true, |(ts, un)| ts <= &timestamp && un != o.user().unwrap())
-                {
-                    last_username.insert(uid, (timestamp, o.user().unwrap().to_owned()));
-                }
+fn serialize_optional_display<S: serde::Serializer>(
+    value: &Option<impl std::fmt::Display>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
 
-                user_edit_days.entry(uid).or_default().insert(day);
-                day_edit_users.entry(day).or_default().insert(uid);
-                (user_edit_days, day_edit_users, last_username)
-            },
-        )
-        .reduce_with(
-            |(mut user_edit_days1, mut day_edit_users1, mut last_username1),
-             (mut user_edit_days2, day_edit_users2, mut last_username2)| {
-                for (uid, days) in user_edit_days2.drain() {
-                    user_edit_days1.entry(uid).or_default().extend(days);
-                }
-                for (day, uids) in day_edit_users2.into_iter() {
-                    day_edit_users1.entry(day).or_default().extend(uids);
-                }
-                for (uid, (ts2, un2)) in last_username2.drain() {
-                    if last_username1
-                        .get(&uid)
-                        .map_or(true, |(ts1, un1)| &ts2 >= ts1 && &un2 != un1)
-                    {
-                        last_username1.insert(uid, (ts2, un2));
-                    }
+/// The command line arguments, with the arguments from the `--config` file (if any) put first, so
+/// that the command line ones override them.
+fn args_with_config(argv: Vec<std::ffi::OsString>) -> Result<Vec<std::ffi::OsString>> {
+    let config_path = argv.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--config" {
+            argv.get(i + 1).cloned()
+        } else {
+            arg.to_str()?.strip_prefix("--config=").map(Into::into)
+        }
+    });
+    let Some(config_path) = config_path else {
+        return Ok(argv);
+    };
+    let config: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_reader(BufReader::new(File::open(&config_path).with_context(
+            || format!("Can't read config file {}", config_path.to_string_lossy()),
+        )?))
+        .with_context(|| format!("Invalid config file {}", config_path.to_string_lossy()))?;
+
+    let mut config_args = Vec::new();
+    for (key, value) in config {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            serde_json::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                serde_json::Value::Bool(true) => config_args.push(flag.clone()),
+                serde_json::Value::Bool(false) | serde_json::Value::Null => {}
+                serde_json::Value::String(value) => config_args.extend([flag.clone(), value]),
+                serde_json::Value::Number(value) => {
+                    config_args.extend([flag.clone(), value.to_string()])
                 }
-                (user_edit_days1, day_edit_users1, last_username1)
-            },
-        )
-        .unwrap();
+                _ => anyhow::bail!(
+                    "Config file value for {} must be a string, number or boolean",
+                    key
+                ),
+            }
+        }
+    }
+    let mut argv = argv.into_iter();
+    Ok(argv
+        .next()
+        .into_iter()
+        .chain(config_args.into_iter().map(Into::into))
+        .chain(argv)
+        .collect())
+}
 
-    input_bar.finish();
-    println!(
-        "All data read in. Have {} users & {} days",
-        user_edit_days.len(),
-        day_edit_users.len()
-    );
+/// Check that this is a valid `strftime` format, since chrono only fails when it's used.
+fn parse_date_format(s: &str) -> Result<String, String> {
+    if chrono::format::StrftimeItems::new(s).any(|item| item == chrono::format::Item::Error) {
+        return Err(format!("{:?} is not a valid strftime format", s));
+    }
+    Ok(s.to_string())
+}
 
-    let input_day_range = (
-        day_edit_users.first_key_value().unwrap().0,
-        day_edit_users.last_key_value().unwrap().0,
-    );
+/// Parse a date range like `2024-03-01..2024-03-15`.
+fn parse_date_range(s: &str) -> Result<std::ops::RangeInclusive<NaiveDate>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("{:?} isn't a date range like 2024-03-01..2024-03-15", s))?;
+    let parse = |date: &str| {
+        date.trim()
+            .parse::<NaiveDate>()
+            .map_err(|err| format!("{:?} isn't a date: {}", date, err))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        return Err(format!("{} is after {}", start, end));
+    }
+    Ok(start..=end)
+}
 
-    let mut output_per_day = csv::Writer::from_writer(BufWriter::new(File::create(format!(
-        "{}user_totals_per_day.csv",
-        args.output_prefix
-    ))?));
-    let mut output_date_per_uid = csv::Writer::from_writer(BufWriter::new(File::create(
-        format!("{}users_per_day.csv", args.output_prefix).to_string(),
-    )?));
-
-    output_per_day.write_record(["date", "num_users", "rolling_yr_total", "users_ge42_days"])?;
-    let year = chrono::Days::new(365);
-    for day in input_day_range
-        .0
-        .iter_days()
-        .take_while(|d| d <= input_day_range.1)
-    {
-        let date_str = day.format("%F").to_string();
-        let total_num_users = day_edit_users
-            .get(&day)
-            .map_or("0".to_string(), |uids| uids.len().to_string());
-        // kinda repeating users_per_day but for last year
-        let uids_last_year: HashMap<u32, HashSet<&NaiveDate>> = day_edit_users
-            .range(day - year..=day)
-            .flat_map(move |(this_day, uids)| uids.iter().map(move |uid| (uid, this_day)))
-            .fold(HashMap::new(), |mut user_totals, (uid, day)| {
-                user_totals.entry(*uid).or_default().insert(day);
-                user_totals
-            });
-        output_per_day.write_record(&[
-            date_str,
-            total_num_users,
-            uids_last_year.len().to_string(),
-            uids_last_year
-                .values()
-                .filter(|days| days.len() >= 42)
-                .count()
-                .to_string(),
-        ])?;
-    }
-
-    output_date_per_uid.write_record([
-        "date",
-        "uid",
-        "num_edit_days_last_yr",
-        "username",
-        "ge42days",
-        "mapped_days",
-    ])?;
-    let start_date = args.start_date.unwrap_or(input_day_range.0.clone());
-    let end_date = args.end_date.unwrap_or(input_day_range.1.clone());
-    let mut start_date = clamp(
-        start_date,
-        input_day_range.0.clone(),
-        input_day_range.1.clone(),
+/// Parse a `--uid-shard` like `0/4`.
+fn parse_uid_shard(s: &str) -> Result<(u32, u32), String> {
+    let (shard, num_shards): (u32, u32) = s
+        .split_once('/')
+        .and_then(|(m, n)| Some((m.trim().parse().ok()?, n.trim().parse().ok()?)))
+        .ok_or_else(|| format!("{:?} isn't a shard like 0/4", s))?;
+    if num_shards == 0 {
+        return Err("there must be at least 1 shard".to_owned());
+    }
+    if shard >= num_shards {
+        return Err(format!(
+            "shard {} doesn't exist, there are only shards 0 to {}",
+            shard,
+            num_shards - 1
+        ));
+    }
+    Ok((shard, num_shards))
+}
+
+/// The longest `--window-days`, about 2700 years, which is more than any history, but keeps the
+/// windows well within the dates `chrono` can have.
+const MAX_WINDOW_DAYS: u64 = 1_000_000;
+
+/// Parse a window length like `365d`, `52w`, `12m` or `1y` into a number of days.
+fn parse_window_days(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "d"),
+    };
+    let num: u64 = num
+        .parse()
+        .map_err(|_| format!("{:?} is not a number followed by d, w, m or y", s))?;
+    let days = match unit.trim() {
+        "d" => Some(num),
+        "w" => num.checked_mul(7),
+        "m" => num.checked_mul(365).map(|days| (days + 6) / 12),
+        "y" => num.checked_mul(365),
+        other => return Err(format!("unknown unit {:?}, expected d, w, m or y", other)),
+    };
+    if days == Some(0) {
+        return Err("window must be at least 1 day".to_string());
+    }
+    let Some(days) = days.filter(|days| *days <= MAX_WINDOW_DAYS) else {
+        return Err(format!(
+            "window can't be longer than {} days",
+            MAX_WINDOW_DAYS
+        ));
+    };
+    Ok(days)
+}
+
+/// All the input files, from `--input-filename` & `--input-list`.
+fn input_filenames(args: &Args) -> Result<Vec<PathBuf>> {
+    let mut filenames = args.input_filename.clone();
+    if let Some(list) = &args.input_list {
+        let file = BufReader::new(
+            File::open(list)
+                .with_context(|| format!("Can't read input list {}", list.display()))?,
+        );
+        for line in file.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            filenames.push(PathBuf::from(line));
+        }
+    }
+    if filenames.is_empty() {
+        anyhow::bail!("No input files given");
+    }
+    Ok(filenames)
+}
+
+/// Read a `--merge-uids` file.
+fn read_merge_uids(path: &PathBuf) -> Result<HashMap<u32, u32>> {
+    let file = BufReader::new(
+        File::open(path)
+            .with_context(|| format!("Can't read merge uids file {}", path.display()))?,
     );
-    let end_date = clamp(
-        end_date,
-        input_day_range.0.clone(),
-        input_day_range.1.clone(),
+    let mut merged_uids = HashMap::new();
+    for (lineno, line) in file.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (old_uid, canonical_uid) = line
+            .split_once('=')
+            .and_then(|(old, canonical)| {
+                Some((old.trim().parse().ok()?, canonical.trim().parse().ok()?))
+            })
+            .with_context(|| {
+                format!(
+                    "{}:{}: expected old_uid=canonical_uid, like 123=456, got {:?}",
+                    path.display(),
+                    lineno + 1,
+                    line
+                )
+            })?;
+        merged_uids.insert(old_uid, canonical_uid);
+    }
+    // Otherwise, what an account is merged into would depend on the order
+    if let Some((old_uid, canonical_uid)) = merged_uids.iter().find(|(old_uid, canonical_uid)| {
+        old_uid != canonical_uid && merged_uids.contains_key(*canonical_uid)
+    }) {
+        anyhow::bail!(
+            "{}: uid {} is merged into {}, which is merged into another uid itself",
+            path.display(),
+            old_uid,
+            canonical_uid
+        );
+    }
+    Ok(merged_uids)
+}
+
+/// The population of each region in a `--population` file.
+fn read_population(path: &PathBuf) -> Result<HashMap<String, f64>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Can't read population file {}", path.display()))?;
+    let mut population = HashMap::new();
+    for row in reader.deserialize() {
+        let (region, num_people): (String, f64) =
+            row.with_context(|| format!("Can't read population file {}", path.display()))?;
+        if num_people <= 0. {
+            anyhow::bail!(
+                "{}: the population of {} is {}, not more than 0",
+                path.display(),
+                region,
+                num_people
+            );
+        }
+        population.insert(region, num_people);
+    }
+    Ok(population)
+}
+
+/// The dates in a `--dates-file`.
+fn read_dates_file(path: &PathBuf) -> Result<BTreeSet<NaiveDate>> {
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("Can't read dates file {}", path.display()))?,
     );
-    if let Some(min_num_days) = args.min_num_days {
-        if end_date - start_date < chrono::TimeDelta::try_days(min_num_days.into()).unwrap() {
-            start_date = start_date - chrono::Days::new(min_num_days.into());
-        }
-    }
-    for specific_date in start_date.iter_days().take_while(|d| d <= &end_date) {
-        let users_days: BTreeMap<u32, BTreeSet<&NaiveDate>> = day_edit_users
-            .range(specific_date - year..=specific_date)
-            .flat_map(|(this_day, uids)| uids.iter().map(move |uid| (uid, this_day)))
-            .fold(Default::default(), |mut user_totals, (uid, day)| {
-                user_totals.entry(*uid).or_default().insert(day);
-                user_totals
-            });
-        let specific_date_str = specific_date.format("%F").to_string();
-        for (uid, days) in users_days.iter() {
-            if days.len() >= args.min_edit_days as usize {
-                output_date_per_uid.write_record([
-                    specific_date_str.as_str(),
-                    &uid.to_string(),
-                    &days.len().to_string(),
-                    &last_username.get(uid).unwrap().1,
-                    if days.len() >= 42 { "yes" } else { "no" },
-                    &days
-                        .iter()
-                        .map(|d| d.format("%d.%m.").to_string())
-                        .collect::<Vec<_>>()
-                        .join(","),
-                ])?;
-            }
+    let mut dates = BTreeSet::new();
+    for (lineno, line) in file.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
+        dates.insert(line.parse().with_context(|| {
+            format!(
+                "{}:{}: expected a date like 2024-01-01, got {:?}",
+                path.display(),
+                lineno + 1,
+                line
+            )
+        })?);
     }
+    Ok(dates)
+}
 
-    println!("Finished");
-    Ok(())
+/// The file formats which can be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InputFormat {
+    Pbf,
+    O5m,
+}
+
+/// Work out the format of this file from its first bytes, and fail early if it's not one which
+/// can be read.
+fn input_format(path: &Path) -> Result<InputFormat> {
+    let mut start = Vec::new();
+    parts::Parts::open(path)?.take(15).read_to_end(&mut start)?;
+    // 4 byte length, then the `type` field (1, string) of the BlobHeader
+    if start.get(4..15) == Some(b"\x0a\x09OSMHeader".as_slice()) {
+        Ok(InputFormat::Pbf)
+    } else if start.starts_with(o5m::MAGIC) {
+        Ok(InputFormat::O5m)
+    } else {
+        Err(Failure::UnrecognizedFormat(path.to_path_buf()).into())
+    }
+}
+
+/// Something which is calculated from every object in the input, in parallel. Each thread
+/// builds up its own `Acc`, and these are merged at the end.
+trait ObjFold: Sync {
+    type Acc: Send;
+    fn init(&self) -> Self::Acc;
+    fn add(&self, acc: &mut Self::Acc, o: &impl OSMObj);
+    fn merge(&self, acc: Self::Acc, other: Self::Acc) -> Self::Acc;
+}
+
+/// Run this calculation over all the input files.
+fn fold_inputs<F: ObjFold>(
+    paths: &[PathBuf],
+    formats: &[InputFormat],
+    bar: &ProgressBar,
+    folder: &F,
+) -> Result<F::Acc> {
+    let mut acc = folder.init();
+    NUM_FOLDED.store(0, Ordering::SeqCst);
+    for (path, format) in paths.iter().zip(formats) {
+        if NUM_FOLDED.load(Ordering::SeqCst) >= MAX_OBJECTS.load(Ordering::SeqCst) {
+            break;
+        }
+        let file = bar.wrap_read(parts::Parts::open(path)?);
+        let num_read = AtomicU64::new(0);
+        let malformed = |problem: String| {
+            Failure::Malformed(
+                path.clone(),
+                format!(
+                    "{}, after reading {} objects",
+                    problem,
+                    num_read.load(Ordering::SeqCst)
+                ),
+            )
+        };
+        let file_acc = match format {
+            InputFormat::Pbf => {
+                // `check_blobs` finds cut off files, but `osmio` panics on corrupt data too. Only
+                // those panics are caught, & not printed, a panic anywhere else is a bug.
+                let decode_panic = Mutex::new(None);
+                let default_hook: Arc<PanicHook> = std::panic::take_hook().into();
+                let hook = default_hook.clone();
+                std::panic::set_hook(Box::new(move |info| {
+                    if !DECODING.get() {
+                        hook(info);
+                    }
+                }));
+                let file_acc = fold_objects(
+                    CatchDecodePanics {
+                        objects: osmio::pbf::PBFReader::new(file).objects(),
+                        panic: &decode_panic,
+                        done: false,
+                    }
+                    .inspect(|_| _ = num_read.fetch_add(1, Ordering::Relaxed)),
+                    folder,
+                );
+                drop(std::panic::take_hook());
+                std::panic::set_hook(Box::new(move |info| default_hook(info)));
+                if let Some(problem) = decode_panic.into_inner().unwrap() {
+                    return Err(malformed(problem).into());
+                }
+                file_acc
+            }
+            InputFormat::O5m => {
+                let mut reader = o5m::O5mReader::new(BufReader::new(file));
+                let file_acc = fold_objects(
+                    (&mut reader).inspect(|_| _ = num_read.fetch_add(1, Ordering::Relaxed)),
+                    folder,
+                );
+                reader
+                    .finish()
+                    .map_err(|err| malformed(format!("{:#}", err)))?;
+                file_acc
+            }
+        };
+        acc = folder.merge(acc, file_acc);
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+    Ok(acc)
+}
+
+type PanicHook = dyn Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send;
+
+thread_local! {
+    /// Set while `osmio` decodes the next object, so that the panic hook doesn't print its
+    /// panics on corrupt data.
+    static DECODING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// The objects, until `osmio` panics on corrupt data. Then it ends, and what's wrong is in
+/// `panic`.
+struct CatchDecodePanics<'a, I> {
+    objects: I,
+    panic: &'a Mutex<Option<String>>,
+    done: bool,
+}
+
+impl<I: Iterator> Iterator for CatchDecodePanics<'_, I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        DECODING.set(true);
+        let next = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.objects.next()));
+        DECODING.set(false);
+        next.unwrap_or_else(|panic| {
+            self.done = true;
+            *self.panic.lock().unwrap() = Some(
+                panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "invalid data".to_string()),
+            );
+            None
+        })
+    }
+}
+
+fn fold_objects<F: ObjFold>(
+    objects: impl Iterator<Item = impl OSMObj + Send> + Send,
+    folder: &F,
+) -> F::Acc {
+    objects
+        .take_while(|_| {
+            !INTERRUPTED.load(Ordering::SeqCst)
+                && NUM_FOLDED.fetch_add(1, Ordering::SeqCst) < MAX_OBJECTS.load(Ordering::SeqCst)
+        })
+        .par_bridge()
+        .fold(
+            || folder.init(),
+            |mut acc, o| {
+                folder.add(&mut acc, &o);
+                acc
+            },
+        )
+        .reduce(|| folder.init(), |acc, other| folder.merge(acc, other))
+}
+
+/// The number of objects, and the first & last timestamp, for `--range-only`. Only the
+/// timestamps are needed, so none of the large maps are built.
+struct RangeFold;
+
+impl ObjFold for RangeFold {
+    type Acc = (u64, i64, i64);
+    fn init(&self) -> Self::Acc {
+        (0, i64::MAX, i64::MIN)
+    }
+    fn add(&self, (num, first, last): &mut Self::Acc, o: &impl OSMObj) {
+        let Some(ts) = obj_timestamp(o) else {
+            return;
+        };
+        *num += 1;
+        *first = (*first).min(ts);
+        *last = (*last).max(ts);
+    }
+    fn merge(
+        &self,
+        (num1, first1, last1): Self::Acc,
+        (num2, first2, last2): Self::Acc,
+    ) -> Self::Acc {
+        (num1 + num2, first1.min(first2), last1.max(last2))
+    }
+}
+
+/// The pass before the main one, collecting the `PrePass`.
+struct PrePassFold {
+    /// From `--min-lat` & `--max-lat`
+    lat_range: Option<std::ops::RangeInclusive<f64>>,
+}
+
+impl ObjFold for PrePassFold {
+    type Acc = PrePass;
+    fn init(&self) -> PrePass {
+        PrePass::default()
+    }
+    fn add(&self, acc: &mut PrePass, o: &impl OSMObj) {
+        if let Some(day) = obj_day(o) {
+            acc.add_obj(o, day);
+        }
+        if let Some(lat_range) = &self.lat_range {
+            if o.as_node()
+                .and_then(|node| node.lat_lon_f64())
+                .is_some_and(|(lat, _)| lat_range.contains(&lat))
+            {
+                acc.lat_range_nodes.insert(o.id());
+            }
+        }
+    }
+    fn merge(&self, acc: PrePass, other: PrePass) -> PrePass {
+        acc.merge(other)
+    }
+}
+
+/// The main pass, collecting the `Stats` of the objects which pass the filter.
+struct StatsFold<'a> {
+    obj_filter: &'a ObjFilter,
+    args: &'a Args,
+    countries: Option<&'a Countries>,
+    regions: Option<&'a Countries>,
+    timezones: Option<&'a TimeZones>,
+    /// `--ignore-future-edits`: The replication timestamp
+    future_after: Option<i64>,
+    /// With `--warn-on-clock-skew`, timestamps before the 1st, or after the 2nd, are wrong
+    clock_skew: Option<(i64, i64)>,
+}
+
+impl ObjFold for StatsFold<'_> {
+    type Acc = Stats;
+    fn init(&self) -> Stats {
+        Stats::default()
+    }
+    fn add(&self, stats: &mut Stats, o: &impl OSMObj) {
+        stats.num_objects += 1;
+        // Redacted objects can have no author information at all
+        let Some(utc_day) =
+            obj_day(o).filter(|_| o.uid().is_some() || self.args.include_redactions)
+        else {
+            stats.redacted_skipped += 1;
+            return;
+        };
+        if let Some((before, after)) = self.clock_skew {
+            match obj_timestamp(o) {
+                Some(ts) if ts < before => stats.add_clock_skewed(0, o, ts),
+                Some(ts) if ts > after => stats.add_clock_skewed(1, o, ts),
+                _ => {}
+            }
+        }
+        if let Some(future_after) = self.future_after {
+            if obj_timestamp(o).is_some_and(|ts| ts > future_after) {
+                stats.future_skipped += 1;
+                return;
+            }
+        }
+        if self.obj_filter.keep(o) {
+            let local_day = self.timezones.and_then(|timezones| {
+                let (lat, lon) = o.as_node()?.lat_lon_f64()?;
+                let timestamp = obj_timestamp(o)?;
+                let local_ts = timestamp + timezones.offset(lon, lat)?;
+                Some(chrono::DateTime::from_timestamp(local_ts, 0)?.date_naive())
+            });
+            let day = local_day.unwrap_or(utc_day);
+            if self.args.exclude_weekends
+                && matches!(day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun)
+            {
+                return;
+            }
+            if self
+                .args
+                .exclude_range
+                .iter()
+                .any(|range| range.contains(&day))
+            {
+                return;
+            }
+            stats.add_obj(o, day, self.args);
+            if let Some(countries) = self.countries {
+                stats.add_country(o, countries);
+            }
+            if let Some(regions) = self.regions {
+                stats.add_region(o, day, regions);
+            }
+        }
+    }
+    fn merge(&self, stats: Stats, other: Stats) -> Stats {
+        stats.merge(other)
+    }
+}
+
+/// `--warn-on-clock-skew` prints this many examples of each kind of wrong timestamp.
+const NUM_CLOCK_SKEW_EXAMPLES: usize = 5;
+
+/// The `users_per_day` rows of this many days are made in parallel, and written, before
+/// starting on the next days, so at most this many days of rows are in memory.
+const ROWS_BATCH_DAYS: usize = 64;
+
+/// Set while reading the input with `--partial-on-interrupt`.
+static READING: AtomicBool = AtomicBool::new(false);
+/// Ctrl-C was pressed while reading the input.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// `--max-objects`: Stop reading after this many objects.
+static MAX_OBJECTS: AtomicU64 = AtomicU64::new(u64::MAX);
+/// Number of objects read in this pass over the input, for `--max-objects`.
+static NUM_FOLDED: AtomicU64 = AtomicU64::new(0);
+/// `--strict`: Stop at the first CSV row which can't be written.
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// Number of CSV rows which couldn't be written, and were skipped.
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+/// `--merge-uids`: The uid each merged account is counted as.
+static MERGED_UIDS: OnceLock<HashMap<u32, u32>> = OnceLock::new();
+
+/// The uid this object is counted as, `ANONYMOUS_UID` if it has none.
+fn obj_uid(o: &impl OSMObj) -> u32 {
+    o.uid().map_or(ANONYMOUS_UID, canonical_uid)
+}
+
+/// With `--merge-uids`, the uid this account is merged into.
+fn canonical_uid(uid: u32) -> u32 {
+    MERGED_UIDS
+        .get()
+        .and_then(|merged_uids| merged_uids.get(&uid).copied())
+        .unwrap_or(uid)
+}
+
+/// The (epoch) timestamp of this object. Timestamps which can't be a date (from corrupt data)
+/// are treated like missing ones.
+fn obj_timestamp(o: &impl OSMObj) -> Option<i64> {
+    let timestamp = o.timestamp().as_ref()?.to_epoch_number();
+    chrono::DateTime::from_timestamp(timestamp, 0).map(|_| timestamp)
+}
+
+/// The (UTC) day this object was edited on, if it has a (valid) timestamp.
+fn obj_day(o: &impl OSMObj) -> Option<NaiveDate> {
+    Some(chrono::DateTime::from_timestamp(obj_timestamp(o)?, 0)?.date_naive())
+}
+
+/// What's needed from the input before the main pass, for `--first-n-days` &
+/// `--exclude-changesets-over`.
+#[derive(Default)]
+struct PrePass {
+    /// Each user's first edit day
+    first_days: HashMap<u32, NaiveDate>,
+    /// Number of objects in each changeset
+    changeset_sizes: HashMap<u32, u64>,
+    /// Nodes which were ever in the `--min-lat`/`--max-lat` range
+    lat_range_nodes: HashSet<ObjId>,
+}
+
+impl PrePass {
+    fn add_obj(&mut self, o: &impl OSMObj, day: NaiveDate) {
+        self.first_days
+            .entry(obj_uid(o))
+            .and_modify(|first| *first = (*first).min(day))
+            .or_insert(day);
+        if let Some(changeset_id) = o.changeset_id() {
+            *self.changeset_sizes.entry(changeset_id).or_default() += 1;
+        }
+    }
+
+    fn merge(mut self, other: PrePass) -> PrePass {
+        for (uid, day) in other.first_days {
+            self.first_days
+                .entry(uid)
+                .and_modify(|first| *first = (*first).min(day))
+                .or_insert(day);
+        }
+        for (changeset_id, size) in other.changeset_sizes {
+            *self.changeset_sizes.entry(changeset_id).or_default() += size;
+        }
+        self.lat_range_nodes.extend(other.lat_range_nodes);
+        self
+    }
+}
+
+/// Username used for a uid which has no username in the file.
+const UNKNOWN_USERNAME: &str = "(unknown)";
+
+/// uid of anonymous edits.
+const ANONYMOUS_UID: u32 = 0;
+
+/// Username used for anonymous edits.
+const ANONYMOUS_USERNAME: &str = "(anonymous)";
+
+const DEFAULT_PROGRESS_TEMPLATE: &str =
+    "[{elapsed_precise}] {eta} {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Period {
+    /// Single days
+    Day,
+    /// ISO weeks, starting on Monday
+    Week,
+    /// Calendar months
+    Month,
+}
+
+impl Period {
+    /// The first day of the period which contains `day`.
+    fn start_of(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => day,
+            Period::Week => day - chrono::Days::new(day.weekday().num_days_from_monday().into()),
+            Period::Month => day.with_day(1).unwrap(),
+        }
+    }
+
+    /// The first day of the period after the one starting on `start`.
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start + chrono::Days::new(1),
+            Period::Week => start + chrono::Days::new(7),
+            Period::Month => start + chrono::Months::new(1),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Format {
+    /// One column per metric
+    Csv,
+    /// Long format, `date,metric,value`
+    Tidy,
+    /// `users_per_day` as an Arrow IPC (Feather) file
+    Arrow,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum ActiveBy {
+    /// Number of days with edits
+    Days,
+    /// Number of edits (object versions)
+    Edits,
+}
+
+impl ActiveBy {
+    /// The end of the active column names.
+    fn unit(&self) -> &'static str {
+        match self {
+            ActiveBy::Days => "days",
+            ActiveBy::Edits => "edits",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+enum Preset {
+    /// Active on at least 42 days in the last 365 calendar days, as in the OSM statistics
+    #[value(name = "osmstats")]
+    #[serde(rename = "osmstats")]
+    OsmStats,
+}
+
+impl Preset {
+    /// Set the arguments of this preset, which weren't given.
+    fn apply(&self, args: &mut Args, matches: &clap::ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
+        match self {
+            Preset::OsmStats => {
+                if unset("window_days") {
+                    args.window_days = 365;
+                }
+                if unset("window_mode") {
+                    args.window_mode = WindowMode::Calendar;
+                }
+                if unset("active_by") {
+                    args.active_by = ActiveBy::Days;
+                }
+                if unset("active_threshold") && args.active_fraction.is_none() {
+                    args.active_threshold = 42;
+                }
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Weight {
+    /// Number of edits (object versions)
+    Edits,
+    /// Number of days with edits
+    Days,
+    /// Number of changesets
+    Changesets,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum EditWeight {
+    /// 1, plus the number of tags of the object version
+    Tags,
+    /// 1 for a node, 2 for a way, 3 for a relation
+    Type,
+}
+
+impl EditWeight {
+    /// How much this object version counts.
+    fn weight(&self, o: &impl OSMObj) -> u64 {
+        match self {
+            EditWeight::Tags => 1 + o.tags().count() as u64,
+            EditWeight::Type => type_index(o.object_type()) as u64 + 1,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum WindowMode {
+    /// The last `--window-days` calendar days
+    Calendar,
+    /// Each user's last `--window-days` edit days
+    ActiveDays,
+    /// The `--window-days` calendar days around the date
+    Centered,
+}
+
+impl WindowMode {
+    /// The days in the rolling window of `day`. It's always `window_days` days (& `day`), even
+    /// with a leap day in it, not a calendar year, so that all windows are as long.
+    fn range(&self, day: NaiveDate, window_days: u64) -> std::ops::RangeInclusive<NaiveDate> {
+        match self {
+            WindowMode::Calendar | WindowMode::ActiveDays => {
+                day.checked_sub_days(chrono::Days::new(window_days))
+                    .unwrap_or(NaiveDate::MIN)..=day
+            }
+            WindowMode::Centered => {
+                let half_window = chrono::Days::new(window_days / 2);
+                day.checked_sub_days(half_window).unwrap_or(NaiveDate::MIN)
+                    ..=day.checked_add_days(half_window).unwrap_or(NaiveDate::MAX)
+            }
+        }
+    }
+}
+
+type UserEditDays = HashMap<u32, BTreeSet<NaiveDate>>;
+type DayEditUsers = BTreeMap<NaiveDate, HashSet<u32>>;
+type LastUsername = HashMap<u32, (i64, String)>;
+type EditorDayUsers = BTreeMap<(String, NaiveDate), HashSet<u32>>;
+/// The version, whether it's deleted, the uid & day, of every version of every object
+type ObjectVersions = HashMap<(u8, ObjId), Vec<(u32, bool, u32, NaiveDate)>>;
+/// The version, the uid, and the hashes of the tags & geometry (`None` if it's deleted), of every
+/// version of every object
+type ObjectTagVersions = HashMap<(u8, ObjId), Vec<(u32, u32, Option<(u64, u64)>)>>;
+/// The number of objects with a wrong timestamp, and the first few of them (by type, id &
+/// version), as type, id, version & timestamp.
+type ClockSkewed = (u64, Vec<(u8, ObjId, u32, i64)>);
+
+/// Everything collected from the input file.
+#[derive(Default)]
+struct Stats {
+    user_edit_days: UserEditDays,
+    day_edit_users: DayEditUsers,
+    /// The username of each user's latest edit which has one, & that edit's timestamp
+    last_username: LastUsername,
+    /// The timestamp of each user's latest edit, whether or not it has a username
+    user_last_timestamp: HashMap<u32, i64>,
+    /// Only filled with `--by-editor`
+    editor_day_users: EditorDayUsers,
+    /// Total number of objects (versions) each user has edited. With `--count-distinct-objects`
+    /// this is only filled in after reading, from `user_objects`.
+    user_num_edits: HashMap<u32, u64>,
+    /// Every object each user has edited. Only filled with `--count-distinct-objects`
+    user_objects: HashMap<u32, HashSet<(u8, ObjId)>>,
+    /// Every object version each user has made. Only filled when reading more than one file, so
+    /// that versions in several (overlapping) files are only counted once.
+    user_versions: HashMap<u32, HashSet<(u8, ObjId, u32)>>,
+    /// Every changeset each user has made
+    user_changesets: HashMap<u32, HashSet<u32>>,
+    /// Number of node, way & relation edits per day. Only filled with `--type-activity`
+    day_type_edits: BTreeMap<NaiveDate, [u64; 3]>,
+    /// Number of registered & anonymous edits per day. Only filled with `--split-anonymous`
+    day_anonymous_edits: BTreeMap<NaiveDate, [u64; 2]>,
+    /// Number of edits per hour of the day (in `--timezone`). Only filled with
+    /// `--hour-histogram`
+    hour_edits: [u64; 24],
+    /// Number of node edits per user in each geohash cell. Only filled with `--geohash-grid`
+    geohash_user_edits: BTreeMap<u64, HashMap<u32, u64>>,
+    /// Everyone who edited each object. Only filled with `--co-edit-graph`
+    object_editors: HashMap<(u8, ObjId), HashSet<u32>>,
+    /// Node, way & relation edits, and changesets, per day, of the `--user`
+    user_day_details: BTreeMap<NaiveDate, ([u64; 3], HashSet<u32>)>,
+    /// Number of edits of each user per day. Only filled with `--bus-factor` &
+    /// `--inequality-metrics` (with `--weight edits`), and `--active-by edits`
+    day_user_edits: BTreeMap<NaiveDate, HashMap<u32, u64>>,
+    /// Number of edits of each user on each day. Only filled with `--user-calendars`,
+    /// `--min-edits-per-day` & `--emit-events-jsonl`
+    user_day_edits: HashMap<u32, BTreeMap<NaiveDate, u64>>,
+    /// The changesets of each user per day. Only filled with `--bus-factor` &
+    /// `--inequality-metrics`, with `--weight changesets`
+    day_user_changesets: BTreeMap<NaiveDate, HashMap<u32, HashSet<u32>>>,
+    /// Only filled with `--track-restorations`
+    object_versions: ObjectVersions,
+    /// Every object each user edited on each day. Only filled with `--track-objects`
+    user_day_objects: HashMap<u32, BTreeMap<NaiveDate, HashSet<(u8, ObjId)>>>,
+    /// The days each user edited an object with tags on. Only filled with `--active-day-expr`
+    /// using `has_tags`
+    user_tagged_days: HashMap<u32, HashSet<NaiveDate>>,
+    /// The tag keys of the objects each user edited on each day. Only filled with
+    /// `--track-tag-keys`
+    user_day_tag_keys: HashMap<u32, BTreeMap<NaiveDate, HashSet<String>>>,
+    /// Only filled with `--track-tag-changes`
+    object_tag_versions: ObjectTagVersions,
+    /// The countries each user edited nodes in. Only filled with `--countries`
+    user_countries: HashMap<u32, HashSet<u16>>,
+    /// The users who edited nodes in each region on each day. Only filled with `--regions`
+    region_day_users: HashMap<u16, DayEditUsers>,
+    /// First & last edit timestamp of each user. Only filled with `--precise-timestamps`
+    user_edit_timestamps: HashMap<u32, (i64, i64)>,
+    /// The bounding box (min lon, min lat, max lon, max lat) of each user's node edits. Only
+    /// filled with `--profiles`
+    user_bboxes: HashMap<u32, [f64; 4]>,
+    /// Only filled with `--weight-by`
+    user_weighted_edits: HashMap<u32, u64>,
+    /// Number of edits per day. Only filled with `--normalize`
+    day_num_edits: BTreeMap<NaiveDate, u64>,
+    /// Users who created an object on each day. Only filled with `--creators`
+    day_creators: BTreeMap<NaiveDate, HashSet<u32>>,
+    /// What each user edited on each day: 1 for nodes, 2 for ways & relations, 3 for both. Only
+    /// filled with `--type-mix`
+    day_user_types: BTreeMap<NaiveDate, HashMap<u32, u8>>,
+    /// Users with a username longer than `--max-username-len`
+    long_username_uids: HashSet<u32>,
+    /// Number of objects read, including ones which were filtered out
+    num_objects: u64,
+    /// Number of objects skipped, because they have no uid or timestamp
+    redacted_skipped: u64,
+    /// Number of objects skipped by `--ignore-future-edits`
+    future_skipped: u64,
+    /// Objects with a timestamp which is too early & too late. Only filled with
+    /// `--warn-on-clock-skew`
+    clock_skewed: [ClockSkewed; 2],
+}
+
+impl Stats {
+    /// Only the edit days, from an `--events-input` file.
+    fn from_day_edit_users(day_edit_users: DayEditUsers) -> Self {
+        let mut user_edit_days = UserEditDays::new();
+        for (day, uids) in day_edit_users.iter() {
+            for uid in uids {
+                user_edit_days.entry(*uid).or_default().insert(*day);
+            }
+        }
+        Stats {
+            user_edit_days,
+            day_edit_users,
+            ..Default::default()
+        }
+    }
+
+    fn add_obj(&mut self, o: &impl OSMObj, day: NaiveDate, args: &Args) {
+        let timestamp = o.timestamp().as_ref().unwrap().to_epoch_number();
+        // Anonymous edits (from before 2007) are all counted as one user, uid 0, which no real
+        // account has. Any username on them is ignored, so they're always called the same.
+        // Other objects can have a blank (or no) username. Then this uid might not have any
+        // username.
+        let uid = obj_uid(o);
+        self.user_last_timestamp
+            .entry(uid)
+            .and_modify(|ts| *ts = (*ts).max(timestamp))
+            .or_insert(timestamp);
+        if uid == ANONYMOUS_UID {
+            self.last_username
+                .entry(uid)
+                .or_insert_with(|| (i64::MIN, ANONYMOUS_USERNAME.to_owned()));
+        } else if let Some(username) = o.user().filter(|un| !un.is_empty()) {
+            let username = match username.char_indices().nth(args.max_username_len) {
+                Some((end, _)) => {
+                    self.long_username_uids.insert(uid);
+                    std::borrow::Cow::Owned(format!("{}…", &username[..end]))
+                }
+                None => std::borrow::Cow::Borrowed(username),
+            };
+            // The objects aren't in time order, so even with the same username, this might be a
+            // later edit. The username is only copied when it's changed.
+            match self.last_username.get_mut(&uid) {
+                None => {
+                    self.last_username
+                        .insert(uid, (timestamp, username.into_owned()));
+                }
+                Some((ts, un)) if *ts <= timestamp => {
+                    *ts = timestamp;
+                    if *un != username {
+                        *un = username.into_owned();
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.user_edit_days.entry(uid).or_default().insert(day);
+        self.day_edit_users.entry(day).or_default().insert(uid);
+        if args.count_distinct_objects {
+            self.user_objects
+                .entry(uid)
+                .or_default()
+                .insert((type_index(o.object_type()) as u8, o.id()));
+        } else if args.input_filename.len() > 1 {
+            self.user_versions.entry(uid).or_default().insert((
+                type_index(o.object_type()) as u8,
+                o.id(),
+                o.version().unwrap_or(0),
+            ));
+        } else {
+            *self.user_num_edits.entry(uid).or_default() += 1;
+        }
+        if let Some(changeset_id) = o.changeset_id() {
+            self.user_changesets
+                .entry(uid)
+                .or_default()
+                .insert(changeset_id);
+        }
+        if let Some((lat, lon)) = o
+            .as_node()
+            .and_then(|node| node.lat_lon_f64())
+            .filter(|_| args.profiles.is_some())
+        {
+            let point = [lon, lat, lon, lat];
+            self.user_bboxes
+                .entry(uid)
+                .and_modify(|bbox| extend_bbox(bbox, point))
+                .or_insert(point);
+        }
+        if let Some(weight_by) = args.weight_by {
+            *self.user_weighted_edits.entry(uid).or_default() += weight_by.weight(o);
+        }
+
+        if args.by_editor {
+            if let Some(editor) = o.tag("created_by") {
+                self.editor_day_users
+                    .entry((editor.to_owned(), day))
+                    .or_default()
+                    .insert(uid);
+            }
+        }
+
+        if args.type_activity {
+            self.day_type_edits.entry(day).or_default()[type_index(o.object_type())] += 1;
+        }
+
+        if args.split_anonymous {
+            self.day_anonymous_edits.entry(day).or_default()[usize::from(uid == ANONYMOUS_UID)] +=
+                1;
+        }
+
+        if args.track_restorations {
+            self.object_versions
+                .entry((type_index(o.object_type()) as u8, o.id()))
+                .or_default()
+                .push((o.version().unwrap_or(0), o.deleted(), uid, day));
+        }
+
+        let active_day_uses = |feature| {
+            args.active_day_expr
+                .as_ref()
+                .is_some_and(|expr| expr.uses(feature))
+        };
+        if args.track_objects
+            || args.dedupe_same_day_object
+            || active_day_uses(Feature::DistinctObjects)
+        {
+            self.user_day_objects
+                .entry(uid)
+                .or_default()
+                .entry(day)
+                .or_default()
+                .insert((type_index(o.object_type()) as u8, o.id()));
+        }
+
+        if args.track_tag_keys {
+            let tag_keys = self
+                .user_day_tag_keys
+                .entry(uid)
+                .or_default()
+                .entry(day)
+                .or_default();
+            for (key, _) in o.tags() {
+                if args.max_tag_keys.is_some_and(|max| tag_keys.len() >= max) {
+                    break;
+                }
+                if !tag_keys.contains(key) {
+                    tag_keys.insert(key.to_owned());
+                }
+            }
+        }
+
+        if args.track_tag_changes {
+            self.object_tag_versions
+                .entry((type_index(o.object_type()) as u8, o.id()))
+                .or_default()
+                .push((
+                    o.version().unwrap_or(0),
+                    uid,
+                    (!o.deleted()).then(|| tag_geometry_hashes(o)),
+                ));
+        }
+
+        if args.precise_timestamps {
+            self.user_edit_timestamps
+                .entry(uid)
+                .and_modify(|(first, last)| {
+                    *first = (*first).min(timestamp);
+                    *last = (*last).max(timestamp);
+                })
+                .or_insert((timestamp, timestamp));
+        }
+
+        if args.normalize {
+            *self.day_num_edits.entry(day).or_default() += 1;
+        }
+
+        if args.creators && o.version() == Some(1) {
+            self.day_creators.entry(day).or_default().insert(uid);
+        }
+
+        if args.type_mix {
+            *self
+                .day_user_types
+                .entry(day)
+                .or_default()
+                .entry(uid)
+                .or_default() |= if o.object_type() == OSMObjectType::Node {
+                1
+            } else {
+                2
+            };
+        }
+
+        if active_day_uses(Feature::HasTags) && o.tags().next().is_some() {
+            self.user_tagged_days.entry(uid).or_default().insert(day);
+        }
+
+        if args.user_calendars.is_some()
+            || args.min_edits_per_day.is_some()
+            || args.emit_events_jsonl.is_some()
+            || active_day_uses(Feature::EditCount)
+        {
+            *self
+                .user_day_edits
+                .entry(uid)
+                .or_default()
+                .entry(day)
+                .or_default() += 1;
+        }
+
+        let concentration = args.bus_factor || args.inequality_metrics;
+        if (concentration && args.weight == Weight::Edits) || args.active_by == ActiveBy::Edits {
+            *self
+                .day_user_edits
+                .entry(day)
+                .or_default()
+                .entry(uid)
+                .or_default() += 1;
+        }
+        if let Some(changeset_id) = o
+            .changeset_id()
+            .filter(|_| concentration && args.weight == Weight::Changesets)
+        {
+            self.day_user_changesets
+                .entry(day)
+                .or_default()
+                .entry(uid)
+                .or_default()
+                .insert(changeset_id);
+        }
+
+        if args.co_edit_graph {
+            self.object_editors
+                .entry((type_index(o.object_type()) as u8, o.id()))
+                .or_default()
+                .insert(uid);
+        }
+
+        if args.user == Some(uid) {
+            let (type_edits, changesets) = self.user_day_details.entry(day).or_default();
+            type_edits[type_index(o.object_type())] += 1;
+            changesets.extend(o.changeset_id());
+        }
+
+        if args.hour_histogram {
+            let local_ts = timestamp + i64::from(args.timezone.local_minus_utc());
+            self.hour_edits[local_ts.rem_euclid(86400) as usize / 3600] += 1;
+        }
+
+        if let Some(precision) = args.geohash_grid {
+            if let Some((lat, lon)) = o.as_node().and_then(|node| node.lat_lon_f64()) {
+                *self
+                    .geohash_user_edits
+                    .entry(geohash(lat, lon, precision))
+                    .or_default()
+                    .entry(uid)
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// Which region this node is in, for `--regions`.
+    fn add_region(&mut self, o: &impl OSMObj, day: NaiveDate, regions: &Countries) {
+        let Some((lat, lon)) = o.as_node().and_then(|node| node.lat_lon_f64()) else {
+            return;
+        };
+        if let Some(region) = regions.find(lon, lat) {
+            self.region_day_users
+                .entry(region)
+                .or_default()
+                .entry(day)
+                .or_default()
+                .insert(obj_uid(o));
+        }
+    }
+
+    /// Which country this node is in, for `--countries`.
+    fn add_country(&mut self, o: &impl OSMObj, countries: &Countries) {
+        let Some((lat, lon)) = o.as_node().and_then(|node| node.lat_lon_f64()) else {
+            return;
+        };
+        if let Some(country) = countries.find(lon, lat) {
+            self.user_countries
+                .entry(obj_uid(o))
+                .or_default()
+                .insert(country);
+        }
+    }
+
+    /// Forget the edit days on which users made fewer than `min_edits` edits. Users without any
+    /// edit days left are removed completely.
+    fn remove_light_days(&mut self, min_edits: u64) {
+        let light_days: Vec<(u32, NaiveDate)> = self
+            .user_day_edits
+            .iter()
+            .flat_map(|(uid, day_edits)| {
+                day_edits
+                    .iter()
+                    .filter(|(_, n)| **n < min_edits)
+                    .map(|(day, _)| (*uid, *day))
+            })
+            .collect();
+        self.remove_user_days(&light_days);
+    }
+
+    /// Forget the edit days for which `expr` isn't true, for `--active-day-expr`. Users without
+    /// any edit days left are removed completely.
+    fn remove_inactive_days(&mut self, expr: &ActiveDayExpr) {
+        let mut inactive_days = Vec::new();
+        for (uid, days) in self.user_edit_days.iter() {
+            for day in days {
+                let features = DayFeatures {
+                    edit_count: self
+                        .user_day_edits
+                        .get(uid)
+                        .and_then(|day_edits| day_edits.get(day))
+                        .copied()
+                        .unwrap_or(0),
+                    distinct_objects: self
+                        .user_day_objects
+                        .get(uid)
+                        .and_then(|day_objects| day_objects.get(day))
+                        .map_or(0, |objects| objects.len() as u64),
+                    has_tags: self
+                        .user_tagged_days
+                        .get(uid)
+                        .is_some_and(|days| days.contains(day)),
+                };
+                if !expr.is_active(&features) {
+                    inactive_days.push((*uid, *day));
+                }
+            }
+        }
+        self.remove_user_days(&inactive_days);
+    }
+
+    /// Forget these edit days of these users, from all the per-user & per-day data. Users
+    /// without any edit days left are removed completely.
+    fn remove_user_days(&mut self, user_days: &[(u32, NaiveDate)]) {
+        let mut no_days_left = HashSet::new();
+        for (uid, day) in user_days {
+            if let Some(days) = self.user_edit_days.get_mut(uid) {
+                days.remove(day);
+                if days.is_empty() {
+                    no_days_left.insert(*uid);
+                }
+            }
+            if let Some(day_uids) = self.day_edit_users.get_mut(day) {
+                day_uids.remove(uid);
+            }
+            if let Some(day_uids) = self.day_creators.get_mut(day) {
+                day_uids.remove(uid);
+            }
+            if let Some(user_edits) = self.day_user_edits.get_mut(day) {
+                user_edits.remove(uid);
+            }
+            if let Some(user_changesets) = self.day_user_changesets.get_mut(day) {
+                user_changesets.remove(uid);
+            }
+            if let Some(user_types) = self.day_user_types.get_mut(day) {
+                user_types.remove(uid);
+            }
+            if let Some(day_edits) = self.user_day_edits.get_mut(uid) {
+                day_edits.remove(day);
+            }
+            if let Some(day_objects) = self.user_day_objects.get_mut(uid) {
+                day_objects.remove(day);
+            }
+            if let Some(days) = self.user_tagged_days.get_mut(uid) {
+                days.remove(day);
+            }
+            if let Some(day_tag_keys) = self.user_day_tag_keys.get_mut(uid) {
+                day_tag_keys.remove(day);
+            }
+        }
+        let user_days: HashSet<&(u32, NaiveDate)> = user_days.iter().collect();
+        self.editor_day_users.retain(|(_, day), day_uids| {
+            day_uids.retain(|uid| !user_days.contains(&(*uid, *day)));
+            !day_uids.is_empty()
+        });
+        for day_users in self.region_day_users.values_mut() {
+            day_users.retain(|day, day_uids| {
+                day_uids.retain(|uid| !user_days.contains(&(*uid, *day)));
+                !day_uids.is_empty()
+            });
+        }
+        self.day_edit_users
+            .retain(|_, day_uids| !day_uids.is_empty());
+        self.day_creators.retain(|_, day_uids| !day_uids.is_empty());
+        self.day_user_edits
+            .retain(|_, user_edits| !user_edits.is_empty());
+        self.day_user_changesets
+            .retain(|_, user_changesets| !user_changesets.is_empty());
+        self.day_user_types
+            .retain(|_, user_types| !user_types.is_empty());
+        self.remove_users(&no_days_left);
+    }
+
+    /// Count this object as having a wrong timestamp, too early (0) or too late (1).
+    fn add_clock_skewed(&mut self, which: usize, o: &impl OSMObj, ts: i64) {
+        let (num, examples) = &mut self.clock_skewed[which];
+        *num += 1;
+        examples.push((
+            type_index(o.object_type()) as u8,
+            o.id(),
+            o.version().unwrap_or(0),
+            ts,
+        ));
+        if examples.len() > NUM_CLOCK_SKEW_EXAMPLES {
+            examples.sort_unstable();
+            examples.truncate(NUM_CLOCK_SKEW_EXAMPLES);
+        }
+    }
+
+    /// Count the objects each user edited on each day, rather than the versions, in the edit
+    /// counts. (`--dedupe-same-day-object`)
+    fn dedupe_same_day_objects(&mut self) {
+        let edits = |objects: &HashSet<(u8, ObjId)>| objects.len() as u64;
+        self.user_num_edits = self
+            .user_day_objects
+            .iter()
+            .map(|(uid, day_objects)| (*uid, day_objects.values().map(edits).sum()))
+            .collect();
+        for (uid, day_edits) in self.user_day_edits.iter_mut() {
+            for (day, num_edits) in day_edits.iter_mut() {
+                *num_edits = edits(&self.user_day_objects[uid][day]);
+            }
+        }
+        for (day, user_edits) in self.day_user_edits.iter_mut() {
+            for (uid, num_edits) in user_edits.iter_mut() {
+                *num_edits = edits(&self.user_day_objects[uid][day]);
+            }
+        }
+        for (day, num_edits) in self.day_num_edits.iter_mut() {
+            *num_edits = self.day_edit_users[day]
+                .iter()
+                .map(|uid| edits(&self.user_day_objects[uid][day]))
+                .sum();
+        }
+    }
+
+    /// Remove everything these users did from the per-user data.
+    fn remove_users(&mut self, uids: &HashSet<u32>) {
+        if uids.is_empty() {
+            return;
+        }
+        self.user_edit_days.retain(|uid, _| !uids.contains(uid));
+        self.day_edit_users.retain(|_, day_uids| {
+            day_uids.retain(|uid| !uids.contains(uid));
+            !day_uids.is_empty()
+        });
+        self.last_username.retain(|uid, _| !uids.contains(uid));
+        self.user_last_timestamp
+            .retain(|uid, _| !uids.contains(uid));
+        self.day_creators.retain(|_, day_uids| {
+            day_uids.retain(|uid| !uids.contains(uid));
+            !day_uids.is_empty()
+        });
+        self.editor_day_users.retain(|_, day_uids| {
+            day_uids.retain(|uid| !uids.contains(uid));
+            !day_uids.is_empty()
+        });
+        self.user_num_edits.retain(|uid, _| !uids.contains(uid));
+        self.user_objects.retain(|uid, _| !uids.contains(uid));
+        self.user_versions.retain(|uid, _| !uids.contains(uid));
+        self.user_changesets.retain(|uid, _| !uids.contains(uid));
+        self.user_bboxes.retain(|uid, _| !uids.contains(uid));
+        self.user_weighted_edits
+            .retain(|uid, _| !uids.contains(uid));
+        self.geohash_user_edits.retain(|_, user_edits| {
+            user_edits.retain(|uid, _| !uids.contains(uid));
+            !user_edits.is_empty()
+        });
+        self.object_editors.retain(|_, editors| {
+            editors.retain(|uid| !uids.contains(uid));
+            !editors.is_empty()
+        });
+        self.user_edit_timestamps
+            .retain(|uid, _| !uids.contains(uid));
+        self.user_countries.retain(|uid, _| !uids.contains(uid));
+        for day_users in self.region_day_users.values_mut() {
+            day_users.retain(|_, day_uids| {
+                day_uids.retain(|uid| !uids.contains(uid));
+                !day_uids.is_empty()
+            });
+        }
+        self.user_day_objects.retain(|uid, _| !uids.contains(uid));
+        self.user_tagged_days.retain(|uid, _| !uids.contains(uid));
+        self.user_day_tag_keys.retain(|uid, _| !uids.contains(uid));
+        self.user_day_edits.retain(|uid, _| !uids.contains(uid));
+        self.day_user_edits.retain(|_, user_edits| {
+            user_edits.retain(|uid, _| !uids.contains(uid));
+            !user_edits.is_empty()
+        });
+        self.day_user_changesets.retain(|_, user_changesets| {
+            user_changesets.retain(|uid, _| !uids.contains(uid));
+            !user_changesets.is_empty()
+        });
+        self.day_user_types.retain(|_, user_types| {
+            user_types.retain(|uid, _| !uids.contains(uid));
+            !user_types.is_empty()
+        });
+        // Not `object_versions` or `object_tag_versions`: the versions by these users are still
+        // what the next versions, by other users, are compared to (e.g. restoring what they
+        // deleted).
+    }
+
+    fn merge(mut self, mut other: Stats) -> Stats {
+        for (uid, days) in other.user_edit_days.drain() {
+            self.user_edit_days.entry(uid).or_default().extend(days);
+        }
+        for (day, uids) in other.day_edit_users.into_iter() {
+            self.day_edit_users.entry(day).or_default().extend(uids);
+        }
+        for (uid, (ts2, un2)) in other.last_username.drain() {
+            if self
+                .last_username
+                .get(&uid)
+                .is_none_or(|(ts1, _)| &ts2 >= ts1)
+            {
+                self.last_username.insert(uid, (ts2, un2));
+            }
+        }
+        for (uid, ts2) in other.user_last_timestamp.drain() {
+            self.user_last_timestamp
+                .entry(uid)
+                .and_modify(|ts1| *ts1 = (*ts1).max(ts2))
+                .or_insert(ts2);
+        }
+        for (key, uids) in other.editor_day_users.into_iter() {
+            self.editor_day_users.entry(key).or_default().extend(uids);
+        }
+        for (day, counts) in other.day_type_edits.into_iter() {
+            let these_counts = self.day_type_edits.entry(day).or_default();
+            for (this, other) in these_counts.iter_mut().zip(counts) {
+                *this += other;
+            }
+        }
+        for (day, counts) in other.day_anonymous_edits.into_iter() {
+            let these_counts = self.day_anonymous_edits.entry(day).or_default();
+            for (this, other) in these_counts.iter_mut().zip(counts) {
+                *this += other;
+            }
+        }
+        for (cell, user_edits) in other.geohash_user_edits.into_iter() {
+            let these_user_edits = self.geohash_user_edits.entry(cell).or_default();
+            for (uid, num_edits) in user_edits {
+                *these_user_edits.entry(uid).or_default() += num_edits;
+            }
+        }
+        for (this, other) in self.hour_edits.iter_mut().zip(other.hour_edits) {
+            *this += other;
+        }
+        for (object, versions) in other.object_versions.drain() {
+            self.object_versions
+                .entry(object)
+                .or_default()
+                .extend(versions);
+        }
+        for (uid, days) in other.user_tagged_days.drain() {
+            self.user_tagged_days.entry(uid).or_default().extend(days);
+        }
+        for (uid, day_objects) in other.user_day_objects.drain() {
+            let these_objects = self.user_day_objects.entry(uid).or_default();
+            for (day, objects) in day_objects {
+                these_objects.entry(day).or_default().extend(objects);
+            }
+        }
+        for (uid, day_tag_keys) in other.user_day_tag_keys.drain() {
+            let these_tag_keys = self.user_day_tag_keys.entry(uid).or_default();
+            for (day, tag_keys) in day_tag_keys {
+                these_tag_keys.entry(day).or_default().extend(tag_keys);
+            }
+        }
+        for (object, versions) in other.object_tag_versions.drain() {
+            self.object_tag_versions
+                .entry(object)
+                .or_default()
+                .extend(versions);
+        }
+        for (region, day_users) in other.region_day_users.drain() {
+            let these_day_users = self.region_day_users.entry(region).or_default();
+            for (day, uids) in day_users {
+                these_day_users.entry(day).or_default().extend(uids);
+            }
+        }
+        for (uid, countries) in other.user_countries.drain() {
+            self.user_countries
+                .entry(uid)
+                .or_default()
+                .extend(countries);
+        }
+        for (uid, (first, last)) in other.user_edit_timestamps.drain() {
+            self.user_edit_timestamps
+                .entry(uid)
+                .and_modify(|(this_first, this_last)| {
+                    *this_first = (*this_first).min(first);
+                    *this_last = (*this_last).max(last);
+                })
+                .or_insert((first, last));
+        }
+        for (day, num_edits) in other.day_num_edits.into_iter() {
+            *self.day_num_edits.entry(day).or_default() += num_edits;
+        }
+        for (day, uids) in other.day_creators.into_iter() {
+            self.day_creators.entry(day).or_default().extend(uids);
+        }
+        for (day, user_types) in other.day_user_types.into_iter() {
+            let these_types = self.day_user_types.entry(day).or_default();
+            for (uid, types) in user_types {
+                *these_types.entry(uid).or_default() |= types;
+            }
+        }
+        for (uid, day_edits) in other.user_day_edits.drain() {
+            let these_edits = self.user_day_edits.entry(uid).or_default();
+            for (day, num_edits) in day_edits {
+                *these_edits.entry(day).or_default() += num_edits;
+            }
+        }
+        for (day, user_edits) in other.day_user_edits.into_iter() {
+            let these_edits = self.day_user_edits.entry(day).or_default();
+            for (uid, num_edits) in user_edits {
+                *these_edits.entry(uid).or_default() += num_edits;
+            }
+        }
+        for (day, user_changesets) in other.day_user_changesets.into_iter() {
+            let these_changesets = self.day_user_changesets.entry(day).or_default();
+            for (uid, changesets) in user_changesets {
+                these_changesets.entry(uid).or_default().extend(changesets);
+            }
+        }
+        self.num_objects += other.num_objects;
+        self.redacted_skipped += other.redacted_skipped;
+        self.future_skipped += other.future_skipped;
+        for (these, (num, examples)) in self.clock_skewed.iter_mut().zip(other.clock_skewed) {
+            these.0 += num;
+            these.1.extend(examples);
+            these.1.sort_unstable();
+            these.1.truncate(NUM_CLOCK_SKEW_EXAMPLES);
+        }
+        self.long_username_uids.extend(other.long_username_uids);
+        for (object, editors) in other.object_editors.drain() {
+            self.object_editors
+                .entry(object)
+                .or_default()
+                .extend(editors);
+        }
+        for (day, (type_edits, changesets)) in other.user_day_details.into_iter() {
+            let (these_edits, these_changesets) = self.user_day_details.entry(day).or_default();
+            for (this, other) in these_edits.iter_mut().zip(type_edits) {
+                *this += other;
+            }
+            these_changesets.extend(changesets);
+        }
+        for (uid, num_edits) in other.user_num_edits.drain() {
+            *self.user_num_edits.entry(uid).or_default() += num_edits;
+        }
+        for (uid, objects) in other.user_objects.drain() {
+            self.user_objects.entry(uid).or_default().extend(objects);
+        }
+        for (uid, versions) in other.user_versions.drain() {
+            self.user_versions.entry(uid).or_default().extend(versions);
+        }
+        for (uid, changesets) in other.user_changesets.drain() {
+            self.user_changesets
+                .entry(uid)
+                .or_default()
+                .extend(changesets);
+        }
+        for (uid, other_bbox) in other.user_bboxes.drain() {
+            self.user_bboxes
+                .entry(uid)
+                .and_modify(|bbox| extend_bbox(bbox, other_bbox))
+                .or_insert(other_bbox);
+        }
+        for (uid, weighted_edits) in other.user_weighted_edits.drain() {
+            *self.user_weighted_edits.entry(uid).or_default() += weighted_edits;
+        }
+        self
+    }
+}
+
+/// Errors with their own exit code, so that scripts can tell them apart.
+#[derive(Debug)]
+enum Failure {
+    /// The input files have no objects at all
+    NoObjects,
+    /// An input file isn't an OSM PBF or o5m file
+    UnrecognizedFormat(PathBuf),
+    /// An input file is malformed, e.g. cut off, with what's wrong
+    Malformed(PathBuf, String),
+    /// There are objects, but the filters removed all of them
+    FilterMatchedNothing(u64),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Failure::NoObjects => write!(f, "No objects found in the input file"),
+            Failure::UnrecognizedFormat(path) => {
+                write!(f, "{} is not an OSM PBF or o5m file", path.display())
+            }
+            Failure::Malformed(path, problem) => {
+                write!(f, "{} is malformed: {}", path.display(), problem)
+            }
+            Failure::FilterMatchedNothing(num_objects) => write!(
+                f,
+                "No objects found (all {} objects in the input were filtered out)",
+                num_objects
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::NoObjects => 3,
+            Failure::UnrecognizedFormat(_) => 4,
+            Failure::FilterMatchedNothing(_) => 5,
+            Failure::Malformed(..) => 7,
+        }
+    }
+}
+
+/// Exit code for reading or writing errors.
+const EXIT_IO_ERROR: u8 = 6;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            if let Some(failure) = err.downcast_ref::<Failure>() {
+                ExitCode::from(failure.exit_code())
+            } else if err.chain().any(|cause| {
+                cause.is::<std::io::Error>()
+                    || cause
+                        .downcast_ref::<csv::Error>()
+                        .is_some_and(|err| err.is_io_error())
+            }) {
+                ExitCode::from(EXIT_IO_ERROR)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let matches =
+        Args::command().get_matches_from(args_with_config(std::env::args_os().collect())?);
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(preset) = args.preset {
+        preset.apply(&mut args, &matches);
+    }
+    args.output_prefix = expand_output_prefix(&args.output_prefix, chrono::Local::now())?;
+    STRICT.store(args.strict, Ordering::SeqCst);
+    let _ = DATE_FORMAT.set(args.date_format.clone());
+    if let Some(max_objects) = args.max_objects {
+        MAX_OBJECTS.store(max_objects, Ordering::SeqCst);
+    }
+    EXCEL_SAFE.store(args.excel_safe, Ordering::SeqCst);
+    let mut timer = Timer::new(args.timing);
+    let mut obj_filter = ObjFilter::new(&args)?;
+    if args.compare.is_some() && (args.first_edit_only || args.format == Format::Tidy) {
+        anyhow::bail!(
+            "--compare needs user_totals_per_day.csv, which isn't written with --first-edit-only \
+             or --format tidy"
+        );
+    }
+    if args.append && args.format == Format::Arrow {
+        anyhow::bail!("--append only works with CSV output, not --format arrow");
+    }
+    if let Some(fraction) = args.active_fraction {
+        if !(0. ..=1.).contains(&fraction) {
+            anyhow::bail!("--active-fraction must be between 0 & 1, not {}", fraction);
+        }
+        if args.active_by == ActiveBy::Edits {
+            anyhow::bail!("--active-fraction is a fraction of days, so it can't be used with --active-by edits");
+        }
+        args.active_threshold = (fraction * args.window_days as f64).ceil() as usize;
+    }
+    if args.events_input.is_empty() {
+        args.input_filename = input_filenames(&args)?;
+    }
+    let output_dates = args.dates_file.as_ref().map(read_dates_file).transpose()?;
+    if let Some(path) = &args.merge_uids {
+        let _ = MERGED_UIDS.set(read_merge_uids(path)?);
+    }
+    if args.explain {
+        println!("{}", serde_json::to_string_pretty(&args)?);
+        return Ok(());
+    }
+    args.output_prefix = output_prefix_dir(std::mem::take(&mut args.output_prefix))?;
+    if args.print_run_hash {
+        // Where the output is written doesn't change it
+        let output_prefix = std::mem::take(&mut args.output_prefix);
+        println!("Run hash: {:016x}", run_hash(&args)?);
+        args.output_prefix = output_prefix;
+    }
+    if let Some(read_from) = args.read_from {
+        eprintln!(
+            "Warning: --read-from skips all edits before {}, so the rolling windows up to {} are \
+             incomplete",
+            read_from,
+            read_from + chrono::Days::new(args.window_days)
+        );
+    }
+    let mut input_len = 0;
+    let mut input_formats = Vec::new();
+    let mut replication_timestamp = None;
+    for path in args.input_filename.iter() {
+        input_formats.push(input_format(path)?);
+        if input_formats.last() == Some(&InputFormat::Pbf) {
+            if let Some(problem) = pbf_header::check_blobs(path)? {
+                return Err(Failure::Malformed(path.clone(), problem).into());
+            }
+        }
+        if input_formats.last() != Some(&InputFormat::Pbf) {
+            // Only PBF files have a replication timestamp
+        } else if let Some(ts) = pbf_header::replication_timestamp(path)? {
+            replication_timestamp = replication_timestamp.max(Some(ts));
+            println!(
+                "{}: data current as of {}",
+                path.display(),
+                chrono::DateTime::from_timestamp(ts, 0)
+                    .map_or(ts.to_string(), |dt| dt.format("%F %T UTC").to_string())
+            );
+        }
+        input_len += parts::len(path)?;
+    }
+    let future_after = replication_timestamp.filter(|_| args.ignore_future_edits);
+    let clock_skew = args.warn_on_clock_skew.then(|| {
+        (
+            args.clock_skew_before
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp(),
+            replication_timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        )
+    });
+    if args.ignore_future_edits && future_after.is_none() {
+        eprintln!(
+            "Warning: --ignore-future-edits does nothing, no input has a replication timestamp"
+        );
+    }
+    let lat_range = (args.min_lat.is_some() || args.max_lat.is_some())
+        .then(|| args.min_lat.unwrap_or(-90.)..=args.max_lat.unwrap_or(90.));
+    let num_passes = if args.first_n_days.is_some()
+        || lat_range.is_some()
+        || args.exclude_changesets_over.is_some()
+        || args.min_changeset_size.is_some()
+        || args.exclude_largest_changeset
+    {
+        2
+    } else {
+        1
+    };
+    let input_bar = ProgressBar::new(input_len * num_passes);
+    input_bar.set_style(
+        ProgressStyle::with_template(&args.progress_template).unwrap_or_else(|err| {
+            eprintln!(
+                "Warning: invalid --progress-template ({}), using the default",
+                err
+            );
+            ProgressStyle::with_template(DEFAULT_PROGRESS_TEMPLATE).unwrap()
+        }),
+    );
+    let refresh_hz = (1000 / args.progress_refresh.max(1)).clamp(1, u8::MAX.into()) as u8;
+    input_bar.set_draw_target(ProgressDrawTarget::stderr_with_hz(refresh_hz));
+
+    if args.range_only {
+        let range = fold_inputs(&args.input_filename, &input_formats, &input_bar, &RangeFold)?;
+        let (num_objects, first_ts, last_ts) = range;
+        input_bar.finish();
+        println!("Objects: {}", num_objects);
+        if num_objects > 0 {
+            let fmt = |ts| {
+                chrono::DateTime::from_timestamp(ts, 0)
+                    .unwrap()
+                    .format("%F %T")
+            };
+            println!("First edit: {}", fmt(first_ts));
+            println!("Last edit: {}", fmt(last_ts));
+        }
+        return Ok(());
+    }
+
+    if num_passes > 1 {
+        let pre_pass = fold_inputs(
+            &args.input_filename,
+            &input_formats,
+            &input_bar,
+            &PrePassFold {
+                lat_range: lat_range.clone(),
+            },
+        )?;
+        if let Some(num_days) = args.first_n_days {
+            obj_filter.first_n_days = Some((chrono::Days::new(num_days), pre_pass.first_days));
+        }
+        if let Some(lat_range) = lat_range {
+            obj_filter.lat_range = Some((lat_range, pre_pass.lat_range_nodes));
+        }
+        let mut largest_changeset = None;
+        if args.exclude_largest_changeset {
+            largest_changeset = pre_pass
+                .changeset_sizes
+                .iter()
+                .max_by_key(|(changeset_id, size)| (**size, std::cmp::Reverse(**changeset_id)))
+                .map(|(changeset_id, size)| (*changeset_id, *size));
+            if let Some((changeset_id, size)) = largest_changeset {
+                println!(
+                    "Excluding the largest changeset, {}, with {} objects",
+                    changeset_id, size
+                );
+            }
+        }
+        obj_filter.excluded_changesets = pre_pass
+            .changeset_sizes
+            .into_iter()
+            .filter(|(changeset_id, size)| {
+                args.exclude_changesets_over.is_some_and(|max| *size > max)
+                    || args.min_changeset_size.is_some_and(|min| *size < min)
+                    || largest_changeset.is_some_and(|(largest, _)| largest == *changeset_id)
+            })
+            .map(|(changeset_id, _)| changeset_id)
+            .collect();
+    }
+
+    if args.partial_on_interrupt {
+        READING.store(true, Ordering::SeqCst);
+        ctrlc::set_handler(|| {
+            if !READING.load(Ordering::SeqCst) || INTERRUPTED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        })?;
+    }
+
+    let countries = args
+        .countries
+        .as_deref()
+        .map(Countries::from_geojson)
+        .transpose()?;
+    let regions = args
+        .regions
+        .as_deref()
+        .map(Countries::from_geojson)
+        .transpose()?;
+    let population = args.population.as_ref().map(read_population).transpose()?;
+    let timezones = args
+        .timezone_per_region
+        .as_deref()
+        .map(TimeZones::from_geojson)
+        .transpose()?;
+    let mut stats = if !args.events_input.is_empty() {
+        let mut day_edit_users = DayEditUsers::new();
+        for path in args.events_input.iter() {
+            for (day, uids) in events_bin::read(path)? {
+                day_edit_users.entry(day).or_default().extend(uids);
+            }
+        }
+        Stats::from_day_edit_users(day_edit_users)
+    } else {
+        fold_inputs(
+            &args.input_filename,
+            &input_formats,
+            &input_bar,
+            &StatsFold {
+                obj_filter: &obj_filter,
+                args: &args,
+                countries: countries.as_ref(),
+                regions: regions.as_ref(),
+                timezones: timezones.as_ref(),
+                future_after,
+                clock_skew,
+            },
+        )?
+    };
+
+    READING.store(false, Ordering::SeqCst);
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        eprintln!("Interrupted, writing partial output from the objects read so far");
+        let filename_start = args.output_prefix.rfind('/').map_or(0, |idx| idx + 1);
+        args.output_prefix.insert_str(filename_start, "PARTIAL_");
+    }
+    if let Some(max_objects) = args
+        .max_objects
+        .filter(|max_objects| NUM_FOLDED.load(Ordering::SeqCst) > *max_objects)
+    {
+        eprintln!(
+            "Warning: Stopped reading after {} objects (--max-objects), so the output is partial",
+            max_objects
+        );
+    }
+
+    if args.count_distinct_objects {
+        stats.user_num_edits = std::mem::take(&mut stats.user_objects)
+            .into_iter()
+            .map(|(uid, objects)| (uid, objects.len() as u64))
+            .collect();
+    } else if args.input_filename.len() > 1 {
+        stats.user_num_edits = std::mem::take(&mut stats.user_versions)
+            .into_iter()
+            .map(|(uid, versions)| (uid, versions.len() as u64))
+            .collect();
+    }
+    if args.dedupe_same_day_object {
+        stats.dedupe_same_day_objects();
+    }
+    if let Some(regex) = &args.exclude_bots_by_name {
+        // Objects with a matching username are skipped while reading, but a user might have
+        // renamed to a matching name later.
+        let bots: HashSet<u32> = stats
+            .last_username
+            .iter()
+            .filter(|(_, (_, username))| regex.is_match(username))
+            .map(|(uid, _)| *uid)
+            .collect();
+        stats.remove_users(&bots);
+    }
+    if args.split_anonymous {
+        stats.remove_users(&HashSet::from([ANONYMOUS_UID]));
+    }
+    if let Some(min_edits) = args.min_edits_per_day {
+        stats.remove_light_days(min_edits);
+    }
+    if let Some(expr) = &args.active_day_expr {
+        stats.remove_inactive_days(expr);
+    }
+    let Stats {
+        user_edit_days,
+        day_edit_users,
+        last_username,
+        user_last_timestamp,
+        editor_day_users,
+        user_num_edits,
+        user_objects: _,
+        user_versions: _,
+        user_changesets,
+        day_type_edits,
+        day_anonymous_edits,
+        hour_edits,
+        geohash_user_edits,
+        object_editors,
+        user_day_details,
+        day_user_edits,
+        user_day_edits,
+        day_user_changesets,
+        object_versions,
+        user_day_objects,
+        user_tagged_days: _,
+        user_day_tag_keys,
+        object_tag_versions,
+        user_countries,
+        region_day_users,
+        user_edit_timestamps,
+        user_bboxes,
+        user_weighted_edits,
+        day_num_edits,
+        day_creators,
+        day_user_types,
+        long_username_uids,
+        num_objects,
+        redacted_skipped,
+        future_skipped,
+        clock_skewed,
+    } = stats;
+
+    input_bar.finish();
+    timer.lap("reading input");
+    println!(
+        "All data read in. Have {} users & {} days",
+        user_edit_days.len(),
+        day_edit_users.len()
+    );
+    if args.profile_memory {
+        match peak_memory_kb() {
+            Some(kb) => println!("Peak memory use: {:.1} MB", kb as f64 / 1024.),
+            None => println!("Peak memory use isn't known on this platform"),
+        }
+    }
+    for uid in long_username_uids.iter().collect::<BTreeSet<_>>() {
+        eprintln!(
+            "Warning: uid {} has a username longer than {} characters, which is truncated",
+            uid, args.max_username_len
+        );
+    }
+    if redacted_skipped > 0 {
+        println!(
+            "Skipped {} redacted objects, without a uid or timestamp",
+            redacted_skipped
+        );
+    }
+    if future_skipped > 0 {
+        println!(
+            "Skipped {} objects with a timestamp after the replication timestamp",
+            future_skipped
+        );
+    }
+    if let Some((_, after)) = clock_skew {
+        let whens = [
+            format!("before {}", args.clock_skew_before),
+            match replication_timestamp {
+                Some(_) => format!("after the replication timestamp, {}", rfc3339(after)),
+                None => format!("in the future, after {}", rfc3339(after)),
+            },
+        ];
+        for ((num, examples), when) in clock_skewed.iter().zip(whens) {
+            if *num == 0 {
+                continue;
+            }
+            let examples: Vec<String> = examples
+                .iter()
+                .map(|(type_idx, id, version, ts)| {
+                    format!(
+                        "{} {} v{} at {}",
+                        ["node", "way", "relation"][*type_idx as usize],
+                        id,
+                        version,
+                        rfc3339(*ts)
+                    )
+                })
+                .collect();
+            eprintln!(
+                "Warning: {} objects have a timestamp {}, e.g. {}",
+                num,
+                when,
+                examples.join(", ")
+            );
+        }
+    }
+
+    // A burst pattern of many edits per changeset, and many edits per day, suggests an import
+    // or bot, rather than someone mapping by hand.
+    let likely_mechanical: HashSet<u32> = user_num_edits
+        .iter()
+        .filter(|(uid, num_edits)| {
+            let num_changesets = user_changesets.get(uid).map_or(0, |c| c.len()) as u64;
+            let num_days = user_edit_days[uid].len() as u64;
+            **num_edits > args.mechanical_edits_per_changeset * num_changesets.max(1)
+                && **num_edits > args.mechanical_edits_per_day * num_days
+        })
+        .map(|(uid, _)| *uid)
+        .collect();
+    let user_num_changesets: HashMap<u32, usize> = if args.profiles.is_some() {
+        user_changesets
+            .iter()
+            .map(|(uid, changesets)| (*uid, changesets.len()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    drop(user_changesets);
+
+    // Number of restorations (a version after a deleted version) per user & day
+    let mut user_day_restorations: HashMap<u32, BTreeMap<NaiveDate, u64>> = HashMap::new();
+    for mut versions in object_versions.into_values() {
+        versions.sort_unstable_by_key(|(version, ..)| *version);
+        // The same version can be in several input files
+        versions.dedup_by_key(|(version, ..)| *version);
+        for pair in versions.windows(2) {
+            let (_, prev_deleted, ..) = pair[0];
+            let (_, deleted, uid, day) = pair[1];
+            if prev_deleted && !deleted {
+                *user_day_restorations
+                    .entry(uid)
+                    .or_default()
+                    .entry(day)
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    // Number of edits which changed the tags, and which changed the geometry, per user
+    let mut user_tag_changes: HashMap<u32, (u64, u64)> = HashMap::new();
+    for mut versions in object_tag_versions.into_values() {
+        versions.sort_unstable_by_key(|(version, ..)| *version);
+        versions.dedup_by_key(|(version, ..)| *version);
+        for pair in versions.windows(2) {
+            // Creations, deletions & restorations don't change anything
+            let (_, _, Some((prev_tags, prev_geometry))) = pair[0] else {
+                continue;
+            };
+            let (_, uid, Some((tags, geometry))) = pair[1] else {
+                continue;
+            };
+            let (tag_edits, geometry_edits) = user_tag_changes.entry(uid).or_default();
+            *tag_edits += u64::from(tags != prev_tags);
+            *geometry_edits += u64::from(geometry != prev_geometry);
+        }
+    }
+
+    if day_edit_users.is_empty() {
+        if num_objects == 0 {
+            return Err(Failure::NoObjects.into());
+        }
+        return Err(Failure::FilterMatchedNothing(num_objects).into());
+    }
+    let input_day_range = (
+        day_edit_users.first_key_value().unwrap().0,
+        day_edit_users.last_key_value().unwrap().0,
+    );
+
+    // Number of users whose first ever edit was on each day
+    let mut new_users_per_day: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+    for days in user_edit_days.values() {
+        *new_users_per_day.entry(*days.first().unwrap()).or_default() += 1;
+    }
+    // …and who edited on at least `--sticky-threshold` more days
+    let mut sticky_new_users_per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    if let Some(sticky_threshold) = args.sticky_threshold {
+        for days in user_edit_days.values() {
+            if days.len() > sticky_threshold {
+                *sticky_new_users_per_day
+                    .entry(*days.first().unwrap())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    if !args.first_edit_only {
+        let users_ge_days_column = format!(
+            "users_ge{}_{}",
+            args.active_threshold,
+            args.active_by.unit()
+        );
+        let (mut output_per_day, mut output_metrics_long) = match args.format {
+            Format::Csv | Format::Arrow => (
+                Some(
+                    OutputCsv::open(
+                        format!("{}user_totals_per_day.csv", args.output_prefix),
+                        args.append,
+                    )?
+                    .rename_column("users_ge42_days", users_ge_days_column.clone()),
+                ),
+                None,
+            ),
+            Format::Tidy => (
+                None,
+                Some(OutputCsv::open(
+                    format!("{}metrics_long.csv", args.output_prefix),
+                    args.append,
+                )?),
+            ),
+        };
+        let mut output_experience_bands = args
+            .experience_bands
+            .then(|| OutputCsv::create(format!("{}experience_bands.csv", args.output_prefix)))
+            .transpose()?;
+        let cohort_years = input_day_range.0.year()..=input_day_range.1.year();
+        let mut output_stacked_cohorts = args
+            .stacked_cohorts
+            .then(|| OutputCsv::create(format!("{}stacked_cohorts.csv", args.output_prefix)))
+            .transpose()?;
+        if let Some(output) = output_stacked_cohorts.as_mut() {
+            // The number of columns depends on the data, so this can't use a record struct
+            output.write_record(
+                std::iter::once("date".to_string())
+                    .chain(cohort_years.clone().map(|year| year.to_string())),
+            )?;
+        }
+
+        let window = |day: NaiveDate| args.window_mode.range(day, args.window_days);
+        let baseline_total = match args.baseline_date {
+            Some(baseline_date) => {
+                if !(input_day_range.0..=input_day_range.1).contains(&&baseline_date) {
+                    anyhow::bail!(
+                        "--baseline-date {} is outside the dates in the input, {} to {}",
+                        baseline_date,
+                        input_day_range.0,
+                        input_day_range.1
+                    );
+                }
+                let baseline_total =
+                    window_users(day_edit_users.range(window(baseline_date))).len();
+                if baseline_total == 0 {
+                    anyhow::bail!(
+                        "No users edited in the window of --baseline-date {}",
+                        baseline_date
+                    );
+                }
+                Some(baseline_total)
+            }
+            None => None,
+        };
+        let mut window_comparison = Vec::new();
+        for end_date in args.compare_windows.iter() {
+            if !(input_day_range.0..=input_day_range.1).contains(&end_date) {
+                anyhow::bail!(
+                    "--compare-windows {} is outside the dates in the input, {} to {}",
+                    end_date,
+                    input_day_range.0,
+                    input_day_range.1
+                );
+            }
+            let range = window(*end_date);
+            let users = window_users(day_edit_users.range(range.clone()));
+            let new = users
+                .iter()
+                .filter(|uid| range.contains(user_edit_days[uid].first().unwrap()))
+                .count();
+            // Users of the window just before this one, who didn't edit in this one
+            let prev_users =
+                window_users(day_edit_users.range(window(*range.start() - chrono::Days::new(1))));
+            let churned = prev_users.difference(&users).count();
+            window_comparison.push([users.len(), new, churned]);
+        }
+        let mut alltime_contributors = 0;
+        let mut validator = Validator::default();
+        // For `--grafana`, the timestamp (in ms) & the numbers of each day
+        let mut grafana_days: Vec<(i64, [usize; 4])> = Vec::new();
+        let mut last_rolling_yr_total = None;
+        // For `--summary`, the (first) day with the most users, and with the highest rolling total
+        let mut peak_num_users = (*input_day_range.0, 0);
+        let mut peak_rolling_yr_total = (*input_day_range.0, 0);
+        let last_day = last_date(&args).unwrap_or(*input_day_range.1);
+        for day in input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1 && *d <= last_day)
+            .take(args.limit_days.unwrap_or(usize::MAX))
+        {
+            let new_contributors = new_users_per_day.get(&day).copied().unwrap_or(0);
+            alltime_contributors += new_contributors;
+            let total_num_users = day_edit_users.get(&day).map_or(0, |uids| uids.len());
+            if total_num_users > peak_num_users.1 {
+                peak_num_users = (day, total_num_users);
+            }
+            if output_dates
+                .as_ref()
+                .is_some_and(|dates| !dates.contains(&day))
+            {
+                continue;
+            }
+            if args.no_rolling {
+                if let Some(output) = output_per_day
+                    .as_mut()
+                    .filter(|output| output.wants_date(day))
+                {
+                    output.serialize(DayNumUsers {
+                        date: day,
+                        num_users: total_num_users,
+                    })?;
+                }
+                if let Some(output) = output_metrics_long
+                    .as_mut()
+                    .filter(|output| output.wants_date(day))
+                {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "num_users",
+                        value: MetricValue::Count(total_num_users as u64),
+                    })?;
+                }
+                continue;
+            }
+            // kinda repeating users_per_day but for last year
+            let uids_last_year = window_user_days(day_edit_users.range(window(day)));
+            // Empty unless `day_user_edits` is filled
+            let user_edits = window_user_edits(day_user_edits.range(window(day)));
+            // What `--bus-factor` & `--inequality-metrics` count
+            let weights = match args.weight {
+                _ if !args.bus_factor && !args.inequality_metrics => Cow::Owned(HashMap::new()),
+                Weight::Edits => Cow::Borrowed(&user_edits),
+                Weight::Days => Cow::Owned(
+                    uids_last_year
+                        .iter()
+                        .map(|(uid, days)| (*uid, days.len() as u64))
+                        .collect(),
+                ),
+                Weight::Changesets => Cow::Owned(window_user_changesets(
+                    day_user_changesets.range(window(day)),
+                )),
+            };
+            let inequality = args.inequality_metrics.then(|| inequality(&weights));
+            let mut day_total = DayTotal {
+                date: day,
+                num_users: total_num_users,
+                rolling_yr_total: uids_last_year.len(),
+                users_ge42_days: match args.active_by {
+                    ActiveBy::Days => uids_last_year
+                        .values()
+                        .filter(|days| days.len() >= args.active_threshold)
+                        .count(),
+                    ActiveBy::Edits => user_edits
+                        .values()
+                        .filter(|num_edits| **num_edits >= args.active_threshold as u64)
+                        .count(),
+                },
+                pct_of_alltime_contributors: percent(
+                    uids_last_year.len() as u64,
+                    match args.window_mode {
+                        // The window includes users who only start editing later
+                        WindowMode::Centered => new_users_per_day
+                            .range(..=*window(day).end())
+                            .map(|(_, n)| n)
+                            .sum(),
+                        WindowMode::Calendar | WindowMode::ActiveDays => alltime_contributors,
+                    },
+                ),
+                median_account_age_days: median(
+                    uids_last_year
+                        .keys()
+                        .map(|uid| (day - *user_edit_days[uid].first().unwrap()).num_days())
+                        .collect(),
+                ),
+                at_risk_users: at_risk_users(&day_edit_users, day),
+                bus_factor: args.bus_factor.then(|| bus_factor(&weights)),
+                gini: inequality.map(|(gini, _)| gini),
+                theil: inequality.map(|(_, theil)| theil),
+                users_per_1k_edits: args.normalize.then(|| {
+                    let num_edits: u64 = day_num_edits.range(window(day)).map(|(_, n)| n).sum();
+                    if num_edits == 0 {
+                        0.
+                    } else {
+                        (uids_last_year.len() as f64 * 100_000. / num_edits as f64).round() / 100.
+                    }
+                }),
+                sticky_new_users: args
+                    .sticky_threshold
+                    .map(|_| sticky_new_users_per_day.get(&day).copied().unwrap_or(0)),
+                creators: None,
+                maintainers: None,
+                node_only_users: None,
+                way_relation_only_users: None,
+                mixed_type_users: None,
+                rolling_yr_index: baseline_total.map(|baseline_total| {
+                    percent(uids_last_year.len() as u64, baseline_total as u64)
+                }),
+                is_outlier: args.flag_outlier_days.map(|factor| {
+                    let trailing_num_users: Vec<i64> = (day - chrono::Days::new(30))
+                        .max(*input_day_range.0)
+                        .iter_days()
+                        .take_while(|d| *d < day)
+                        .map(|d| day_edit_users.get(&d).map_or(0, |uids| uids.len() as i64))
+                        .collect();
+                    !trailing_num_users.is_empty()
+                        && total_num_users as f64 > factor * median(trailing_num_users)
+                }),
+            };
+            if args.creators {
+                // With `--min-edits-per-day`, the day they created something on might not count
+                let creators = day_creators
+                    .range(window(day))
+                    .flat_map(|(_, uids)| uids)
+                    .filter(|uid| uids_last_year.contains_key(uid))
+                    .collect::<HashSet<_>>()
+                    .len();
+                day_total.creators = Some(creators);
+                day_total.maintainers = Some(uids_last_year.len() - creators);
+            }
+            if args.type_mix {
+                let mut user_types: HashMap<u32, u8> = HashMap::new();
+                for (_, types) in day_user_types.range(window(day)) {
+                    for (uid, types) in types {
+                        // With `--min-edits-per-day`, some of these days might not count
+                        if uids_last_year.contains_key(uid) {
+                            *user_types.entry(*uid).or_default() |= types;
+                        }
+                    }
+                }
+                let num_users = |types| user_types.values().filter(|t| **t == types).count();
+                day_total.node_only_users = Some(num_users(1));
+                day_total.way_relation_only_users = Some(num_users(2));
+                day_total.mixed_type_users = Some(num_users(3));
+            }
+            if args.validate {
+                validator.check(&day_total, alltime_contributors);
+            }
+            let skip_day = args.skip_empty_days
+                && day_total.num_users == 0
+                && last_rolling_yr_total == Some(day_total.rolling_yr_total);
+            last_rolling_yr_total = Some(day_total.rolling_yr_total);
+            if day_total.rolling_yr_total > peak_rolling_yr_total.1 {
+                peak_rolling_yr_total = (day, day_total.rolling_yr_total);
+            }
+            if args.grafana.is_some() && !skip_day {
+                // Users of the window the day before, who haven't edited since it
+                let this_window = window(day);
+                let churned_users = day_edit_users
+                    .range(window(day - chrono::Days::new(1)))
+                    .take_while(|(this_day, _)| *this_day < this_window.start())
+                    .flat_map(|(_, uids)| uids)
+                    .filter(|uid| {
+                        user_edit_days[uid]
+                            .range(this_window.clone())
+                            .next()
+                            .is_none()
+                    })
+                    .collect::<HashSet<_>>()
+                    .len();
+                grafana_days.push((
+                    day.and_hms_opt(0, 0, 0)
+                        .unwrap()
+                        .and_utc()
+                        .timestamp_millis(),
+                    [
+                        day_total.num_users,
+                        new_contributors as usize,
+                        churned_users,
+                        day_total.rolling_yr_total,
+                    ],
+                ));
+            }
+            if let Some(output) = output_per_day
+                .as_mut()
+                .filter(|output| !skip_day && output.wants_date(day))
+            {
+                output.serialize(&day_total)?;
+            }
+            if let Some(output) = output_metrics_long
+                .as_mut()
+                .filter(|output| !skip_day && output.wants_date(day))
+            {
+                for (metric, value) in [
+                    ("num_users", MetricValue::Count(day_total.num_users as u64)),
+                    (
+                        "rolling_yr_total",
+                        MetricValue::Count(day_total.rolling_yr_total as u64),
+                    ),
+                    (
+                        users_ge_days_column.as_str(),
+                        MetricValue::Count(day_total.users_ge42_days as u64),
+                    ),
+                    (
+                        "pct_of_alltime_contributors",
+                        MetricValue::Decimal(day_total.pct_of_alltime_contributors),
+                    ),
+                    (
+                        "median_account_age_days",
+                        MetricValue::Decimal(day_total.median_account_age_days),
+                    ),
+                    (
+                        "at_risk_users",
+                        MetricValue::Count(day_total.at_risk_users as u64),
+                    ),
+                    ("new_contributors", MetricValue::Count(new_contributors)),
+                    (
+                        "alltime_contributors",
+                        MetricValue::Count(alltime_contributors),
+                    ),
+                ] {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric,
+                        value,
+                    })?;
+                }
+                if let Some(users_per_1k_edits) = day_total.users_per_1k_edits {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "users_per_1k_edits",
+                        value: MetricValue::Decimal(users_per_1k_edits),
+                    })?;
+                }
+                if let Some(sticky_new_users) = day_total.sticky_new_users {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "sticky_new_users",
+                        value: MetricValue::Count(sticky_new_users as u64),
+                    })?;
+                }
+                if let Some(rolling_yr_index) = day_total.rolling_yr_index {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "rolling_yr_index",
+                        value: MetricValue::Decimal(rolling_yr_index),
+                    })?;
+                }
+                if let (Some(node_only), Some(way_relation_only), Some(mixed)) = (
+                    day_total.node_only_users,
+                    day_total.way_relation_only_users,
+                    day_total.mixed_type_users,
+                ) {
+                    for (metric, num_users) in [
+                        ("node_only_users", node_only),
+                        ("way_relation_only_users", way_relation_only),
+                        ("mixed_type_users", mixed),
+                    ] {
+                        output.serialize(MetricLong {
+                            date: day,
+                            metric,
+                            value: MetricValue::Count(num_users as u64),
+                        })?;
+                    }
+                }
+                if let Some(is_outlier) = day_total.is_outlier {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "is_outlier",
+                        value: MetricValue::Count(is_outlier.into()),
+                    })?;
+                }
+                if let (Some(creators), Some(maintainers)) =
+                    (day_total.creators, day_total.maintainers)
+                {
+                    for (metric, num_users) in
+                        [("creators", creators), ("maintainers", maintainers)]
+                    {
+                        output.serialize(MetricLong {
+                            date: day,
+                            metric,
+                            value: MetricValue::Count(num_users as u64),
+                        })?;
+                    }
+                }
+                if let Some(bus_factor) = day_total.bus_factor {
+                    output.serialize(MetricLong {
+                        date: day,
+                        metric: "bus_factor",
+                        value: MetricValue::Count(bus_factor as u64),
+                    })?;
+                }
+                if let Some((gini, theil)) = inequality {
+                    for (metric, value) in [("gini", gini), ("theil", theil)] {
+                        output.serialize(MetricLong {
+                            date: day,
+                            metric,
+                            value: MetricValue::Decimal(value),
+                        })?;
+                    }
+                }
+            }
+
+            if let Some(output) = output_experience_bands.as_mut() {
+                // Bands are [0, 1), [1, 3), [3, 5) & [5, ∞) years
+                let mut bands = [0; 4];
+                for uid in uids_last_year.keys() {
+                    let tenure_days = (day - *user_edit_days[uid].first().unwrap()).num_days();
+                    let band = match tenure_days / 365 {
+                        0 => 0,
+                        1..=2 => 1,
+                        3..=4 => 2,
+                        _ => 3,
+                    };
+                    bands[band] += 1;
+                }
+                output.serialize(ExperienceBands {
+                    date: day,
+                    tenure_0_1y: bands[0],
+                    tenure_1_3y: bands[1],
+                    tenure_3_5y: bands[2],
+                    tenure_5y_plus: bands[3],
+                })?;
+            }
+
+            if let Some(output) = output_stacked_cohorts.as_mut() {
+                let mut cohorts = vec![0; cohort_years.clone().count()];
+                for uid in uids_last_year.keys() {
+                    let first_year = user_edit_days[uid].first().unwrap().year();
+                    cohorts[(first_year - cohort_years.start()) as usize] += 1;
+                }
+                output.write_record(
+                    std::iter::once(format_date(&day))
+                        .chain(cohorts.iter().map(|num| num.to_string())),
+                )?;
+            }
+        }
+        if args.summary {
+            println!(
+                "Peak day: {} users on {}",
+                peak_num_users.1, peak_num_users.0
+            );
+            if !args.no_rolling {
+                println!(
+                    "Peak rolling total: {} users on {}",
+                    peak_rolling_yr_total.1, peak_rolling_yr_total.0
+                );
+            }
+        }
+        if let [a, b] = window_comparison.as_slice() {
+            println!(
+                "{:<14}{:>12}{:>12}{:>8}",
+                "Window ending",
+                args.compare_windows[0].to_string(),
+                args.compare_windows[1].to_string(),
+                "Change"
+            );
+            for (i, name) in ["Active users", "New users", "Churned users"]
+                .iter()
+                .enumerate()
+            {
+                println!(
+                    "{:<14}{:>12}{:>12}{:>+8}",
+                    name,
+                    a[i],
+                    b[i],
+                    b[i] as i64 - a[i] as i64
+                );
+            }
+        }
+        if let Some(output) = output_per_day {
+            output.finish()?;
+            timer.lap("user_totals_per_day.csv");
+            if let Some(old_filename) = &args.compare {
+                compare_csvs(
+                    old_filename,
+                    &PathBuf::from(format!("{}user_totals_per_day.csv", args.output_prefix)),
+                )?;
+            }
+        }
+        if args.validate {
+            validator.finish()?;
+        }
+        if let Some(path) = &args.grafana {
+            let targets: Vec<GrafanaTarget> = [
+                "num_users",
+                "new_users",
+                "churned_users",
+                "rolling_yr_total",
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(i, target)| GrafanaTarget {
+                target,
+                datapoints: grafana_days
+                    .iter()
+                    .map(|(timestamp_ms, values)| (values[i], *timestamp_ms))
+                    .collect(),
+            })
+            .collect();
+            write_atomically(path, |output| Ok(serde_json::to_writer(output, &targets)?))?;
+            timer.lap("grafana");
+        }
+        if let Some(output) = output_metrics_long {
+            output.finish()?;
+            timer.lap("metrics_long.csv");
+        }
+        if let Some(output) = output_experience_bands {
+            output.finish()?;
+        }
+        if let Some(output) = output_stacked_cohorts {
+            output.finish()?;
+            timer.lap("stacked_cohorts.csv");
+        }
+
+        if !args.no_rolling {
+            let (start_date, end_date) = output_date_range(
+                args.start_date,
+                last_date(&args),
+                (*input_day_range.0, *input_day_range.1),
+                args.min_num_days,
+            );
+            let extension = if args.format == Format::Arrow {
+                "arrow"
+            } else {
+                "csv"
+            };
+            let users_per_day_filename = |year: i32| {
+                if args.partition_by_year {
+                    format!("{}users_per_day.{}.{}", args.output_prefix, year, extension)
+                } else {
+                    format!("{}users_per_day.{}", args.output_prefix, extension)
+                }
+            };
+            let mut output_year = start_date.year();
+            let ge_days_column = format!("ge{}{}", args.active_threshold, args.active_by.unit());
+            let create_users_per_day = |year| {
+                let filename = users_per_day_filename(year);
+                Ok::<_, anyhow::Error>(if args.format == Format::Arrow {
+                    UsersPerDayOutput::Arrow(ArrowUserDays::create(
+                        filename,
+                        &ge_days_column,
+                        OptionalColumns {
+                            edit_day_bitmap: args.emit_bitmap,
+                            countries_edited: args.countries.is_some(),
+                            edit_timestamps: args.precise_timestamps,
+                            restorations: args.track_restorations,
+                            objects_touched: args.track_objects,
+                            distinct_tag_keys: args.track_tag_keys,
+                        },
+                    )?)
+                } else {
+                    UsersPerDayOutput::Csv(
+                        OutputCsv::open(filename, args.append)?
+                            .rename_column("ge42days", ge_days_column.clone()),
+                    )
+                })
+            };
+            // Each day's rows are made in parallel, and written in order by this thread
+            let rows_for_date = |specific_date: NaiveDate| {
+                let mut rows = Vec::new();
+                let user_edits = (args.active_by == ActiveBy::Edits)
+                    .then(|| window_user_edits(day_user_edits.range(window(specific_date))));
+                let users_days: BTreeMap<u32, BTreeSet<&NaiveDate>> = day_edit_users
+                    .range(window(specific_date))
+                    .flat_map(|(this_day, uids)| uids.iter().map(move |uid| (uid, this_day)))
+                    .fold(Default::default(), |mut user_totals, (uid, day)| {
+                        user_totals.entry(*uid).or_default().insert(day);
+                        user_totals
+                    });
+                for (uid, days) in users_days.iter() {
+                    let active_days: BTreeSet<&NaiveDate>;
+                    let days = match args.window_mode {
+                        WindowMode::Calendar | WindowMode::Centered => days,
+                        WindowMode::ActiveDays => {
+                            active_days = user_edit_days[uid]
+                                .range(..=specific_date)
+                                .rev()
+                                .take(args.window_days as usize)
+                                .collect();
+                            &active_days
+                        }
+                    };
+                    let first_edit = user_edit_days[uid].first().unwrap();
+                    if args.only_new_in_range && !(start_date..=end_date).contains(first_edit) {
+                        continue;
+                    }
+                    if days.len() >= args.min_edit_days as usize
+                        && args
+                            .max_edit_days
+                            .is_none_or(|max_days| days.len() <= max_days as usize)
+                        && (args.min_total_edit_days == 0
+                            || user_edit_days[uid].range(..=specific_date).count()
+                                >= args.min_total_edit_days as usize)
+                    {
+                        rows.push(UserDay {
+                            date: specific_date,
+                            uid: *uid,
+                            num_edit_days_last_yr: days.len(),
+                            username: last_username
+                                .get(uid)
+                                .map_or(UNKNOWN_USERNAME, |(_, un)| un.as_str()),
+                            ge42days: match &user_edits {
+                                Some(user_edits) => {
+                                    user_edits.get(uid).copied().unwrap_or(0)
+                                        >= args.active_threshold as u64
+                                }
+                                None => days.len() >= args.active_threshold,
+                            },
+                            mapped_days: days
+                                .iter()
+                                .map(|d| format_date(d))
+                                .collect::<Vec<_>>()
+                                .join(","),
+                            likely_mechanical: likely_mechanical.contains(uid),
+                            account_age_days: (specific_date - *first_edit).num_days(),
+                            density: density(days),
+                            pattern: edit_pattern(days, &args),
+                            edit_day_bitmap: args.emit_bitmap.then(|| {
+                                let window = window(specific_date);
+                                edit_day_bitmap(days, *window.start(), *window.end())
+                            }),
+                            countries_edited: args
+                                .countries
+                                .is_some()
+                                .then(|| user_countries.get(uid).map_or(0, |c| c.len())),
+                            first_edit_ts: user_edit_timestamps
+                                .get(uid)
+                                .map(|(first, _)| rfc3339(*first)),
+                            last_edit_ts: user_edit_timestamps
+                                .get(uid)
+                                .map(|(_, last)| rfc3339(*last)),
+                            restorations: args.track_restorations.then(|| {
+                                user_day_restorations
+                                    .get(uid)
+                                    .map_or(0, |day_restorations| {
+                                        day_restorations
+                                            .range(window(specific_date))
+                                            .map(|(_, n)| n)
+                                            .sum()
+                                    })
+                            }),
+                            objects_touched: args.track_objects.then(|| {
+                                user_day_objects.get(uid).map_or(0, |day_objects| {
+                                    day_objects
+                                        .range(window(specific_date))
+                                        .flat_map(|(_, objects)| objects)
+                                        .collect::<HashSet<_>>()
+                                        .len()
+                                })
+                            }),
+                            distinct_tag_keys: args.track_tag_keys.then(|| {
+                                user_day_tag_keys.get(uid).map_or(0, |day_tag_keys| {
+                                    let num_keys = day_tag_keys
+                                        .range(window(specific_date))
+                                        .flat_map(|(_, tag_keys)| tag_keys)
+                                        .collect::<HashSet<_>>()
+                                        .len();
+                                    args.max_tag_keys.map_or(num_keys, |max| num_keys.min(max))
+                                })
+                            }),
+                        });
+                    }
+                }
+                if let Some(top_n) = args.top_n_per_day.filter(|top_n| rows.len() > *top_n) {
+                    let most_days_first = |a: &UserDay, b: &UserDay| {
+                        (b.num_edit_days_last_yr, a.uid).cmp(&(a.num_edit_days_last_yr, b.uid))
+                    };
+                    if top_n > 0 {
+                        rows.select_nth_unstable_by(top_n - 1, most_days_first);
+                    }
+                    rows.truncate(top_n);
+                    rows.sort_unstable_by_key(|row| row.uid);
+                }
+                rows
+            };
+            let mut output_date_per_uid = create_users_per_day(output_year)?;
+            // With `--append`, skip the days already in the file
+            let dates: Vec<NaiveDate> = start_date
+                .iter_days()
+                .take_while(|d| d <= &end_date)
+                .take(args.limit_days.unwrap_or(usize::MAX))
+                .filter(|d| output_dates.as_ref().is_none_or(|dates| dates.contains(d)))
+                .filter(|d| match &output_date_per_uid {
+                    UsersPerDayOutput::Csv(output) => {
+                        args.partition_by_year || output.wants_date(*d)
+                    }
+                    UsersPerDayOutput::Arrow(_) => true,
+                })
+                .collect();
+            // The rows are streamed, in date order: only the rows of one batch of days are in
+            // memory at once, never all of them
+            for batch in dates.chunks(ROWS_BATCH_DAYS) {
+                let batch_rows: Vec<Vec<UserDay>> = batch
+                    .par_iter()
+                    .map(|specific_date| rows_for_date(*specific_date))
+                    .collect();
+                for (specific_date, rows) in batch.iter().zip(batch_rows) {
+                    if args.partition_by_year && specific_date.year() != output_year {
+                        output_year = specific_date.year();
+                        std::mem::replace(
+                            &mut output_date_per_uid,
+                            create_users_per_day(output_year)?,
+                        )
+                        .finish()?;
+                    }
+                    if let UsersPerDayOutput::Csv(output) = &output_date_per_uid {
+                        if !output.wants_date(*specific_date) {
+                            continue;
+                        }
+                    }
+                    for row in rows {
+                        output_date_per_uid.serialize(row)?;
+                    }
+                }
+            }
+            output_date_per_uid.finish()?;
+            timer.lap(&format!("users_per_day.{}", extension));
+        }
+    }
+
+    if args.net_flow {
+        write_net_flow(
+            format!("{}net_flow.csv", args.output_prefix),
+            args.net_flow_period,
+            &user_edit_days,
+            &day_edit_users,
+        )?;
+        timer.lap("net_flow.csv");
+    }
+
+    if let Some(period) = args.resample {
+        write_resampled(
+            format!("{}resampled.csv", args.output_prefix),
+            period,
+            chrono::Days::new(args.window_days),
+            &day_edit_users,
+        )?;
+        timer.lap("resampled.csv");
+    }
+
+    if args.by_editor {
+        let mut output = OutputCsv::create(format!("{}editor_usage.csv", args.output_prefix))?;
+        for ((editor, day), uids) in editor_day_users.iter() {
+            output.serialize(EditorUsage {
+                editor,
+                date: *day,
+                num_users: uids.len(),
+            })?;
+        }
+        output.finish()?;
+        timer.lap("editor_usage.csv");
+    }
+
+    if args.type_activity {
+        let mut output = OutputCsv::create(format!("{}type_activity.csv", args.output_prefix))?;
+        for day in input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+        {
+            let [node_edits, way_edits, relation_edits] =
+                day_type_edits.get(&day).copied().unwrap_or_default();
+            output.serialize(TypeActivity {
+                date: day,
+                node_edits,
+                way_edits,
+                relation_edits,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("type_activity.csv");
+    }
+
+    if let (Some((first_day, _)), Some((last_day, _))) = (
+        day_anonymous_edits.first_key_value(),
+        day_anonymous_edits.last_key_value(),
+    ) {
+        let mut output = OutputCsv::create(format!("{}anonymous_per_day.csv", args.output_prefix))?;
+        for day in first_day.iter_days().take_while(|d| d <= last_day) {
+            let [registered_edits, anonymous_edits] =
+                day_anonymous_edits.get(&day).copied().unwrap_or_default();
+            output.serialize(AnonymousDay {
+                date: day,
+                registered_edits,
+                anonymous_edits,
+                pct_anonymous: percent(anonymous_edits, registered_edits + anonymous_edits),
+            })?;
+        }
+        output.finish()?;
+        timer.lap("anonymous_per_day.csv");
+    }
+
+    if args.first_edit_only {
+        let mut output = OutputCsv::create(format!("{}acquisition.csv", args.output_prefix))?;
+        let mut cumulative = 0;
+        for day in input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+        {
+            let new_contributors = new_users_per_day.get(&day).copied().unwrap_or(0);
+            cumulative += new_contributors;
+            output.serialize(Acquisition {
+                date: day,
+                new_contributors,
+                cumulative,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("acquisition.csv");
+    }
+
+    if args.year_overlap {
+        let mut year_users: BTreeMap<i32, HashSet<u32>> = BTreeMap::new();
+        for (day, uids) in day_edit_users.iter() {
+            year_users.entry(day.year()).or_default().extend(uids);
+        }
+        let mut output = OutputCsv::create(format!("{}year_overlap.csv", args.output_prefix))?;
+        let empty = HashSet::new();
+        let mut prev_users = &empty;
+        for year in input_day_range.0.year()..=input_day_range.1.year() {
+            let users = year_users.get(&year).unwrap_or(&empty);
+            output.serialize(YearOverlap {
+                year,
+                carried_over: users.intersection(prev_users).count(),
+                new: users.difference(prev_users).count(),
+                lost: prev_users.difference(users).count(),
+            })?;
+            prev_users = users;
+        }
+        output.finish()?;
+        timer.lap("year_overlap.csv");
+    }
+
+    if let Some(n_months) = args.retention_n_months {
+        // Users active in each month, by number of months since year 0
+        let mut month_users: BTreeMap<i32, HashSet<u32>> = BTreeMap::new();
+        for (day, uids) in day_edit_users.iter() {
+            month_users
+                .entry(day.year() * 12 + day.month0() as i32)
+                .or_default()
+                .extend(uids);
+        }
+        let last_month = input_day_range.1.year() * 12 + input_day_range.1.month0() as i32;
+        let empty = HashSet::new();
+        let rates: Vec<f64> = month_users
+            .iter()
+            .filter(|(month, _)| **month + n_months as i32 <= last_month)
+            .map(|(month, users)| {
+                let later_users = month_users
+                    .get(&(month + n_months as i32))
+                    .unwrap_or(&empty);
+                users.intersection(later_users).count() as f64 / users.len() as f64
+            })
+            .collect();
+        if rates.is_empty() {
+            println!(
+                "Retention after {} months: the input doesn't span {} months",
+                n_months, n_months
+            );
+        } else {
+            println!(
+                "Retention after {} months: {:.2}% (average over {} months)",
+                n_months,
+                rates.iter().sum::<f64>() * 100. / rates.len() as f64,
+                rates.len()
+            );
+        }
+    }
+
+    if args.survival {
+        let month = |day: &NaiveDate| day.year() * 12 + day.month0() as i32;
+        let last_month = month(input_day_range.1);
+        // The number of months from each user's first to their last edit, and whether they
+        // stopped, or are still active in the last month of the file (censored)
+        let durations: Vec<(usize, bool)> = user_edit_days
+            .values()
+            .map(|days| {
+                let (first, last) = (month(days.first().unwrap()), month(days.last().unwrap()));
+                ((last - first) as usize, last < last_month)
+            })
+            .collect();
+        let mut output = OutputCsv::create(format!("{}survival.csv", args.output_prefix))?;
+        output.serialize(Survival {
+            months_since_first_edit: 0,
+            at_risk: durations.len(),
+            survival: 1.,
+        })?;
+        let mut survival = 1.;
+        for months in 1.. {
+            // Everyone who was still active the month before could have stopped
+            let at_risk = durations.iter().filter(|(d, _)| *d >= months - 1).count();
+            if at_risk == 0 {
+                break;
+            }
+            let stopped = durations
+                .iter()
+                .filter(|(d, stopped)| *stopped && *d == months - 1)
+                .count();
+            survival *= 1. - stopped as f64 / at_risk as f64;
+            output.serialize(Survival {
+                months_since_first_edit: months,
+                at_risk,
+                survival: (survival * 10_000.).round() / 10_000.,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("survival.csv");
+    }
+
+    if let Some(num_days) = args.since_first_edit_window {
+        // How many users edited on each day since their first edit, & how many could have (the
+        // input doesn't end before that day)
+        let mut num_active = vec![0; num_days];
+        let mut num_users = vec![0; num_days];
+        for days in user_edit_days.values() {
+            let first = *days.first().unwrap();
+            let num_seen = ((*input_day_range.1 - first).num_days() as usize + 1).min(num_days);
+            for users in &mut num_users[..num_seen] {
+                *users += 1;
+            }
+            for day in days {
+                if let Some(active) = num_active.get_mut((*day - first).num_days() as usize) {
+                    *active += 1;
+                }
+            }
+        }
+        let mut output = OutputCsv::create(format!("{}newcomer_activity.csv", args.output_prefix))?;
+        for (days_since_first, (num_users, num_active)) in
+            num_users.into_iter().zip(num_active).enumerate()
+        {
+            if num_users == 0 {
+                break;
+            }
+            output.serialize(NewcomerActivity {
+                days_since_first,
+                num_users,
+                active_fraction: (num_active as f64 / num_users as f64 * 10_000.).round() / 10_000.,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("newcomer_activity.csv");
+    }
+
+    if args.autocorrelation {
+        let num_users: Vec<f64> = input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+            .map(|day| {
+                day_edit_users
+                    .get(&day)
+                    .map_or(0., |uids| uids.len() as f64)
+            })
+            .collect();
+        for lag in [7, 365] {
+            match autocorrelation(&num_users, lag) {
+                Some(r) => println!(
+                    "Autocorrelation of users per day, lag {} days: {:.3}",
+                    lag, r
+                ),
+                None => println!(
+                    "Autocorrelation of users per day, lag {} days: none, the input is too short or constant",
+                    lag
+                ),
+            }
+        }
+    }
+
+    if args.funnel {
+        let spans: Vec<i64> = user_edit_days
+            .values()
+            .map(|days| (*days.last().unwrap() - *days.first().unwrap()).num_days())
+            .collect();
+        let num_users = spans.len() as u64;
+        println!("Funnel: {} users edited on at least 1 day", num_users);
+        for min_span in [7, 30, 365] {
+            let num = spans.iter().filter(|span| **span >= min_span).count() as u64;
+            println!(
+                "Funnel: {} users ({}%) with at least {} days between first & last edit",
+                num,
+                percent(num, num_users),
+                min_span
+            );
+        }
+    }
+
+    if args.cohort_quality {
+        // Number of users, and of those who edited on more than 1 day, per first edit month
+        let mut cohorts: BTreeMap<(i32, u32), (u64, u64)> = BTreeMap::new();
+        for days in user_edit_days.values() {
+            let first_edit = days.first().unwrap();
+            let (cohort_size, returned) = cohorts
+                .entry((first_edit.year(), first_edit.month()))
+                .or_default();
+            *cohort_size += 1;
+            if days.len() > 1 {
+                *returned += 1;
+            }
+        }
+        let mut output = OutputCsv::create(format!("{}cohort_quality.csv", args.output_prefix))?;
+        for ((year, month), (cohort_size, returned)) in cohorts {
+            output.serialize(CohortQuality {
+                month: format!("{:04}-{:02}", year, month),
+                cohort_size,
+                returned,
+                pct_returned: percent(returned, cohort_size),
+            })?;
+        }
+        output.finish()?;
+        timer.lap("cohort_quality.csv");
+    }
+
+    if args.hour_histogram {
+        let mut output = OutputCsv::create(format!("{}hour_histogram.csv", args.output_prefix))?;
+        for (hour, num_edits) in hour_edits.iter().enumerate() {
+            output.serialize(HourHistogram {
+                hour,
+                num_edits: *num_edits,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("hour_histogram.csv");
+    }
+
+    if let Some(precision) = args.geohash_grid {
+        let mut output = OutputCsv::create(format!("{}geohash_counts.csv", args.output_prefix))?;
+        for (cell, user_edits) in geohash_user_edits.iter() {
+            output.serialize(GeohashCount {
+                geohash: geohash_string(*cell, precision),
+                distinct_users: user_edits.len(),
+                edit_count: user_edits.values().sum(),
+            })?;
+        }
+        output.finish()?;
+        timer.lap("geohash_counts.csv");
+    }
+
+    if args.window_distribution {
+        let mut output =
+            OutputCsv::create(format!("{}window_distribution.csv", args.output_prefix))?;
+        let window = chrono::Days::new(args.window_days);
+        let sample_dates = input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+            .filter(|d| {
+                d.succ_opt().is_none_or(|next| next.month() != d.month()) || d == input_day_range.1
+            });
+        for date in sample_dates {
+            let mut user_num_days: HashMap<u32, usize> = HashMap::new();
+            for uids in day_edit_users
+                .range(date - window..=date)
+                .map(|(_, uids)| uids)
+            {
+                for uid in uids {
+                    *user_num_days.entry(*uid).or_default() += 1;
+                }
+            }
+            let max_days = user_num_days.values().copied().max().unwrap_or(0);
+            let mut histogram = vec![0; max_days + 1];
+            for num_days in user_num_days.values() {
+                histogram[*num_days] += 1;
+            }
+            for (num_edit_days, num_users) in histogram.into_iter().enumerate().skip(1) {
+                output.serialize(WindowDistribution {
+                    date,
+                    num_edit_days,
+                    num_users,
+                })?;
+            }
+        }
+        output.finish()?;
+        timer.lap("window_distribution.csv");
+    }
+
+    if args.tier_transitions {
+        let mut output = OutputCsv::create(format!("{}tier_transitions.csv", args.output_prefix))?;
+        // 0 is inactive (not in the window), then casual, regular & core
+        let tier = |num_days: usize| {
+            if num_days >= args.active_threshold {
+                3
+            } else if num_days >= 10 {
+                2
+            } else {
+                1
+            }
+        };
+        let month_ends = input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+            .filter(|d| {
+                d.succ_opt().is_none_or(|next| next.month() != d.month()) || d == input_day_range.1
+            });
+        let mut prev_tiers: HashMap<u32, u8> = HashMap::new();
+        for date in month_ends {
+            let mut user_num_days: HashMap<u32, usize> = HashMap::new();
+            for (_, uids) in day_edit_users.range(args.window_mode.range(date, args.window_days)) {
+                for uid in uids {
+                    *user_num_days.entry(*uid).or_default() += 1;
+                }
+            }
+            let tiers: HashMap<u32, u8> = user_num_days
+                .into_iter()
+                .map(|(uid, num_days)| (uid, tier(num_days)))
+                .collect();
+            let mut num_users = [0; 4];
+            for tier in tiers.values() {
+                num_users[*tier as usize] += 1;
+            }
+            let (mut moved_up, mut moved_down) = (0, 0);
+            for uid in tiers
+                .keys()
+                .chain(prev_tiers.keys())
+                .collect::<HashSet<_>>()
+            {
+                let (prev, now) = (
+                    prev_tiers.get(uid).copied().unwrap_or(0),
+                    tiers.get(uid).copied().unwrap_or(0),
+                );
+                if now > prev {
+                    moved_up += 1;
+                } else if now < prev {
+                    moved_down += 1;
+                }
+            }
+            output.serialize(TierTransitions {
+                date,
+                casual: num_users[1],
+                regular: num_users[2],
+                core: num_users[3],
+                moved_up,
+                moved_down,
+            })?;
+            prev_tiers = tiers;
+        }
+        output.finish()?;
+        timer.lap("tier_transitions.csv");
+    }
+
+    if let Some(path) = &args.profiles {
+        write_atomically(path, |output| {
+            let mut uids: Vec<_> = user_edit_days.keys().copied().collect();
+            uids.sort_unstable();
+            for uid in uids {
+                let days = &user_edit_days[&uid];
+                let (mut longest_streak_days, mut streak) = (1, 1);
+                let mut longest_gap_days = None;
+                for (prev, next) in days.iter().zip(days.iter().skip(1)) {
+                    let gap = (*next - *prev).num_days();
+                    streak = if gap == 1 { streak + 1 } else { 1 };
+                    longest_streak_days = longest_streak_days.max(streak);
+                    longest_gap_days = longest_gap_days.max(Some(gap));
+                }
+                let years_active = days
+                    .iter()
+                    .map(|day| day.year())
+                    .collect::<HashSet<_>>()
+                    .len();
+                let span_years = days.last().unwrap().year() - days.first().unwrap().year() + 1;
+                serde_json::to_writer(
+                    &mut *output,
+                    &UserProfile {
+                        uid,
+                        username: last_username
+                            .get(&uid)
+                            .map_or(UNKNOWN_USERNAME, |(_, un)| un.as_str()),
+                        first_edit: *days.first().unwrap(),
+                        last_edit: *days.last().unwrap(),
+                        last_edit_ts: rfc3339(user_last_timestamp[&uid]),
+                        num_edit_days: days.len(),
+                        longest_streak_days,
+                        longest_gap_days,
+                        years_active,
+                        consistency: (years_active as f64 * 100. / span_years as f64).round()
+                            / 100.,
+                        num_edits: user_num_edits.get(&uid).copied().unwrap_or(0),
+                        num_changesets: user_num_changesets.get(&uid).copied().unwrap_or(0),
+                        bbox: user_bboxes.get(&uid).copied(),
+                        weighted_edits: args
+                            .weight_by
+                            .map(|_| user_weighted_edits.get(&uid).copied().unwrap_or(0)),
+                    },
+                )?;
+                output.write_all(b"\n")?;
+            }
+            Ok(())
+        })?;
+        timer.lap("profiles");
+    }
+
+    if args.inter_arrival {
+        let mut output = OutputCsv::create(format!("{}inter_arrival.csv", args.output_prefix))?;
+        let mut uids: Vec<_> = user_edit_days.keys().copied().collect();
+        uids.sort_unstable();
+        for uid in uids {
+            let days = &user_edit_days[&uid];
+            let mut gaps: Vec<i64> = days
+                .iter()
+                .zip(days.iter().skip(1))
+                .map(|(prev, next)| (*next - *prev).num_days())
+                .collect();
+            let gaps_list = gaps
+                .iter()
+                .map(|gap| gap.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let mean_gap_days = (!gaps.is_empty()).then(|| {
+                (gaps.iter().sum::<i64>() as f64 * 100. / gaps.len() as f64).round() / 100.
+            });
+            gaps.sort_unstable();
+            let median_gap_days = (!gaps.is_empty()).then(|| {
+                let mid = gaps.len() / 2;
+                if gaps.len().is_multiple_of(2) {
+                    (gaps[mid - 1] + gaps[mid]) as f64 / 2.
+                } else {
+                    gaps[mid] as f64
+                }
+            });
+            output.serialize(InterArrival {
+                uid,
+                username: last_username
+                    .get(&uid)
+                    .map_or(UNKNOWN_USERNAME, |(_, un)| un.as_str()),
+                num_edit_days: days.len(),
+                mean_gap_days,
+                median_gap_days,
+                max_gap_days: gaps.last().copied(),
+                gaps: gaps_list,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("inter_arrival.csv");
+    }
+
+    if args.track_tag_changes {
+        let mut output = OutputCsv::create(format!("{}tag_changes.csv", args.output_prefix))?;
+        let mut uids: Vec<_> = user_edit_days.keys().copied().collect();
+        uids.sort_unstable();
+        for uid in uids {
+            let (tag_edits, geometry_edits) =
+                user_tag_changes.get(&uid).copied().unwrap_or_default();
+            output.serialize(TagChanges {
+                uid,
+                username: last_username
+                    .get(&uid)
+                    .map_or(UNKNOWN_USERNAME, |(_, un)| un.as_str()),
+                tag_edits,
+                geometry_edits,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("tag_changes.csv");
+    }
+
+    if args.reactivation {
+        let mut output = OutputCsv::create(format!("{}reactivation.csv", args.output_prefix))?;
+        let mut uids: Vec<_> = user_edit_days.keys().copied().collect();
+        uids.sort_unstable();
+        for uid in uids {
+            let days = &user_edit_days[&uid];
+            for (prev, next) in days.iter().zip(days.iter().skip(1)) {
+                let gap_days = (*next - *prev).num_days();
+                if gap_days >= args.reactivation_gap_days {
+                    output.serialize(Reactivation {
+                        uid,
+                        username: last_username
+                            .get(&uid)
+                            .map_or(UNKNOWN_USERNAME, |(_, un)| un.as_str()),
+                        last_edit_before: *prev,
+                        return_date: *next,
+                        gap_days,
+                    })?;
+                }
+            }
+        }
+        output.finish()?;
+        timer.lap("reactivation.csv");
+    }
+
+    if args.emit_events {
+        let mut output = OutputCsv::create(format!("{}edit_events.csv", args.output_prefix))?;
+        let mut uids: Vec<_> = user_edit_days.keys().copied().collect();
+        uids.sort_unstable();
+        for uid in uids {
+            for date in user_edit_days[&uid].iter() {
+                output.serialize(EditEvent { uid, date: *date })?;
+            }
+        }
+        output.finish()?;
+        timer.lap("edit_events.csv");
+    }
+
+    if let Some(path) = &args.emit_events_jsonl {
+        let mut last_date = None;
+        if args.append && path.exists() {
+            let existing = std::fs::read_to_string(path)
+                .with_context(|| format!("Can't read {} to append to it", path.display()))?;
+            if let Some(line) = existing.lines().rfind(|line| !line.trim().is_empty()) {
+                let event: EditEventJson = serde_json::from_str(line).with_context(|| {
+                    format!("Can't append to {}, invalid last line", path.display())
+                })?;
+                last_date = Some(event.date);
+            }
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(last_date.is_some())
+            .truncate(last_date.is_none())
+            .open(path)
+            .with_context(|| format!("Can't create {}", path.display()))?;
+        let mut output = BufWriter::new(file);
+        let new_days = match last_date {
+            Some(last_date) => day_edit_users.range(last_date.succ_opt().unwrap()..),
+            None => day_edit_users.range(..),
+        };
+        for (date, uids) in new_days {
+            let mut uids: Vec<_> = uids.iter().copied().collect();
+            uids.sort_unstable();
+            for uid in uids {
+                serde_json::to_writer(
+                    &mut output,
+                    &EditEventJson {
+                        uid,
+                        date: *date,
+                        num_edits: user_day_edits[&uid][date],
+                    },
+                )?;
+                output.write_all(b"\n")?;
+            }
+            output.flush()?;
+        }
+        timer.lap("edit events JSON");
+    }
+
+    if let Some(path) = &args.emit_events_bin {
+        events_bin::write(path, &day_edit_users)?;
+        timer.lap("edit events binary");
+    }
+
+    if let Some(regions) = regions.as_ref().filter(|_| args.region_timeseries) {
+        let mut output = OutputCsv::create(format!("{}region_timeseries.csv", args.output_prefix))?;
+        let (start_date, end_date) = output_date_range(
+            args.start_date,
+            last_date(&args),
+            (*input_day_range.0, *input_day_range.1),
+            args.min_num_days,
+        );
+        let no_users = DayEditUsers::new();
+        for region in 0..regions.len() {
+            let name = match regions.properties(region)["name"].as_str() {
+                Some(name) => name.to_owned(),
+                None => region.to_string(),
+            };
+            let num_people =
+                match &population {
+                    Some(population) => Some(*population.get(&name).with_context(|| {
+                        format!("Region {} isn't in the --population file", name)
+                    })?),
+                    None => None,
+                };
+            let day_users = region_day_users.get(&region).unwrap_or(&no_users);
+            for date in start_date.iter_days().take_while(|date| *date <= end_date) {
+                let rolling_yr_total = day_users
+                    .range(args.window_mode.range(date, args.window_days))
+                    .flat_map(|(_, uids)| uids)
+                    .collect::<HashSet<_>>()
+                    .len();
+                output.serialize(RegionTimeseries {
+                    region: &name,
+                    date,
+                    active_users: day_users.get(&date).map_or(0, |uids| uids.len()),
+                    rolling_yr_total,
+                    active_per_100k: num_people.map(|num_people| {
+                        (rolling_yr_total as f64 * 10_000_000. / num_people).round() / 100.
+                    }),
+                })?;
+            }
+        }
+        output.finish()?;
+        timer.lap("region_timeseries.csv");
+    }
+
+    if let Some(dir) = &args.user_calendars {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Can't create directory {}", dir.display()))?;
+        let end_date = clamp(
+            last_date(&args).unwrap_or(*input_day_range.1),
+            *input_day_range.0,
+            *input_day_range.1,
+        );
+        let start_date = end_date
+            .checked_sub_days(chrono::Days::new(args.window_days))
+            .map_or(*input_day_range.0, |start| start.max(*input_day_range.0));
+        let mut uids: Vec<_> = user_edit_days
+            .iter()
+            .filter(|(_, days)| {
+                days.range(start_date..=end_date).count() >= args.min_edit_days as usize
+            })
+            .map(|(uid, _)| *uid)
+            .collect();
+        uids.sort_unstable();
+        for uid in uids.iter() {
+            let day_edits = &user_day_edits[uid];
+            let mut output = OutputCsv::create(format!("{}/{}.csv", dir.display(), uid))?;
+            for date in start_date.iter_days().take_while(|date| *date <= end_date) {
+                output.serialize(CalendarDay {
+                    date,
+                    num_edits: day_edits.get(&date).copied().unwrap_or(0),
+                })?;
+            }
+            output.finish()?;
+        }
+        println!("Wrote {} user calendars to {}", uids.len(), dir.display());
+        timer.lap("user calendars");
+    }
+
+    if args.heatmap {
+        let mut week_users: BTreeMap<(i32, u32), HashSet<u32>> = BTreeMap::new();
+        for (day, uids) in day_edit_users.iter() {
+            let week = day.iso_week();
+            week_users
+                .entry((week.year(), week.week()))
+                .or_default()
+                .extend(uids);
+        }
+        let years = input_day_range.0.iso_week().year()..=input_day_range.1.iso_week().year();
+        // The number of columns depends on the data, so this can't use a record struct
+        let mut output = OutputCsv::create(format!("{}heatmap.csv", args.output_prefix))?;
+        output.write_record(
+            std::iter::once("week".to_string()).chain(years.clone().map(|y| y.to_string())),
+        )?;
+        for week in 1..=53 {
+            output.write_record(std::iter::once(week.to_string()).chain(years.clone().map(
+                |year| {
+                    week_users
+                        .get(&(year, week))
+                        .map_or(0, |uids| uids.len())
+                        .to_string()
+                },
+            )))?;
+        }
+        output.finish()?;
+        timer.lap("heatmap.csv");
+    }
+
+    if args.isoweek {
+        let mut output = OutputCsv::create(format!("{}isoweek.csv", args.output_prefix))?;
+        let mut week = Period::Week.start_of(*input_day_range.0);
+        while week <= *input_day_range.1 {
+            let next_week = Period::Week.next(week);
+            let active_users = day_edit_users
+                .range(week..next_week)
+                .flat_map(|(_, uids)| uids)
+                .collect::<HashSet<_>>()
+                .len();
+            let iso_week = week.iso_week();
+            output.serialize(IsoWeek {
+                iso_year: iso_week.year(),
+                iso_week: iso_week.week(),
+                active_users,
+                new_users: new_users_per_day
+                    .range(week..next_week)
+                    .map(|(_, n)| n)
+                    .sum(),
+            })?;
+            week = next_week;
+        }
+        output.finish()?;
+        timer.lap("isoweek.csv");
+    }
+
+    if args.seasonality {
+        // Number of days, and total number of users on them, in each month
+        let mut months = [(0, 0); 12];
+        for day in input_day_range
+            .0
+            .iter_days()
+            .take_while(|d| d <= input_day_range.1)
+        {
+            let month = &mut months[day.month0() as usize];
+            month.0 += 1;
+            month.1 += day_edit_users.get(&day).map_or(0, |uids| uids.len());
+        }
+        let mean = |num_days: usize, num_users: usize| num_users as f64 / num_days as f64;
+        let (total_days, total_users) = months.iter().fold((0, 0), |(days, users), month| {
+            (days + month.0, users + month.1)
+        });
+        let overall_mean = mean(total_days, total_users);
+        let round = |x: f64| (x * 100.).round() / 100.;
+        let mut output = OutputCsv::create(format!("{}seasonality.csv", args.output_prefix))?;
+        for (month0, (num_days, num_users)) in months.into_iter().enumerate() {
+            let month_mean = (num_days > 0).then(|| mean(num_days, num_users));
+            output.serialize(Seasonality {
+                month: month0 as u32 + 1,
+                num_days,
+                mean_daily_users: month_mean.map(round),
+                seasonality_index: month_mean
+                    .filter(|_| overall_mean > 0.)
+                    .map(|month_mean| round(month_mean / overall_mean)),
+            })?;
+        }
+        output.finish()?;
+        timer.lap("seasonality.csv");
+    }
+
+    if args.co_edit_graph {
+        let mut edges: HashMap<(u32, u32), u64> = HashMap::new();
+        for editors in object_editors.values() {
+            if editors.len() < 2 || editors.len() > args.max_object_editors {
+                continue;
+            }
+            let mut editors: Vec<u32> = editors.iter().copied().collect();
+            editors.sort_unstable();
+            for (i, uid_a) in editors.iter().enumerate() {
+                for uid_b in editors[i + 1..].iter() {
+                    *edges.entry((*uid_a, *uid_b)).or_default() += 1;
+                }
+            }
+        }
+        drop(object_editors);
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort_unstable();
+        let mut output = OutputCsv::create(format!("{}co_edit_graph.csv", args.output_prefix))?;
+        for ((uid_a, uid_b), shared_objects) in edges {
+            output.serialize(CoEdit {
+                uid_a,
+                uid_b,
+                shared_objects,
+            })?;
+        }
+        output.finish()?;
+        timer.lap("co_edit_graph.csv");
+    }
+
+    if let Some(uid) = args.user {
+        let filename = format!("user_{}.csv", uid);
+        let mut output = OutputCsv::create(format!("{}{}", args.output_prefix, filename))?;
+        for (day, ([node_edits, way_edits, relation_edits], changesets)) in user_day_details.iter()
+        {
+            output.serialize(UserDetail {
+                date: *day,
+                num_edits: node_edits + way_edits + relation_edits,
+                num_changesets: changesets.len(),
+                node_edits: *node_edits,
+                way_edits: *way_edits,
+                relation_edits: *relation_edits,
+            })?;
+        }
+        output.finish()?;
+        if user_day_details.is_empty() {
+            eprintln!("Warning: uid {} has no edits in the input", uid);
+        }
+        timer.lap(&filename);
+    }
+
+    let write_errors = WRITE_ERRORS.load(Ordering::SeqCst);
+    if write_errors > 0 {
+        eprintln!(
+            "Warning: {} rows couldn't be written, and are missing from the output",
+            write_errors
+        );
+    }
+
+    timer.total();
+    println!("Finished");
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        // The output is incomplete, so don't claim success
+        std::process::exit(130);
+    }
+    Ok(())
+}
+
+/// Write the per-period balance sheet of contributors.
+///
+/// A user is *new* in the period of their first ever edit, *returning* if they are active in
+/// this period, weren't in the previous one, but have edited before, and *churned* if they were
+/// active in the previous period but not in this one.
+fn write_net_flow(
+    filename: String,
+    period: Period,
+    user_edit_days: &UserEditDays,
+    day_edit_users: &DayEditUsers,
+) -> Result<()> {
+    let mut output = OutputCsv::create(filename)?;
+
+    let mut period_users: BTreeMap<NaiveDate, HashSet<u32>> = BTreeMap::new();
+    for (day, uids) in day_edit_users.iter() {
+        period_users
+            .entry(period.start_of(*day))
+            .or_default()
+            .extend(uids);
+    }
+    let (first_period, last_period) = match (
+        period_users.first_key_value(),
+        period_users.last_key_value(),
+    ) {
+        (Some((first, _)), Some((last, _))) => (*first, *last),
+        _ => return Ok(()),
+    };
+
+    let empty = HashSet::new();
+    let mut prev_users = &empty;
+    let mut this_period = first_period;
+    while this_period <= last_period {
+        let users = period_users.get(&this_period).unwrap_or(&empty);
+        let (mut new, mut returning) = (0, 0);
+        for uid in users.difference(prev_users) {
+            if period.start_of(*user_edit_days[uid].first().unwrap()) == this_period {
+                new += 1;
+            } else {
+                returning += 1;
+            }
+        }
+        let churned = prev_users.difference(users).count() as u64;
+        output.serialize(NetFlow {
+            period: this_period,
+            new,
+            returning,
+            churned,
+            net: new as i64 + returning as i64 - churned as i64,
+        })?;
+
+        prev_users = users;
+        this_period = period.next(this_period);
+    }
+    output.finish()?;
+
+    Ok(())
+}
+
+/// Write the number of users in every period from the first to the last edit, and the rolling
+/// total on the last day of it (or of the input, for the last period).
+fn write_resampled(
+    filename: String,
+    period: Period,
+    window: chrono::Days,
+    day_edit_users: &DayEditUsers,
+) -> Result<()> {
+    let mut output = OutputCsv::create(filename)?;
+    let (Some((first_day, _)), Some((last_day, _))) = (
+        day_edit_users.first_key_value(),
+        day_edit_users.last_key_value(),
+    ) else {
+        return Ok(());
+    };
+
+    let mut this_period = period.start_of(*first_day);
+    while this_period <= *last_day {
+        let next_period = period.next(this_period);
+        let end = next_period.pred_opt().unwrap().min(*last_day);
+        let num_users = day_edit_users
+            .range(this_period..next_period)
+            .flat_map(|(_, uids)| uids)
+            .collect::<HashSet<_>>()
+            .len();
+        let rolling_yr_total = day_edit_users
+            .range(end - window..=end)
+            .flat_map(|(_, uids)| uids)
+            .collect::<HashSet<_>>()
+            .len();
+        output.serialize(Resampled {
+            period: this_period,
+            num_users,
+            rolling_yr_total,
+        })?;
+        this_period = next_period;
+    }
+    output.finish()?;
+
+    Ok(())
+}
+
+/// Print the differences between 2 CSV files with a `date` column, matching rows by date, and
+/// the other columns by name.
+fn compare_csvs(old_filename: &PathBuf, new_filename: &PathBuf) -> Result<()> {
+    let read = |filename: &PathBuf| -> Result<_> {
+        let mut reader = csv::Reader::from_path(filename)
+            .with_context(|| format!("Can't read {}", filename.display()))?;
+        let headers = reader.headers()?.clone();
+        let date_col = headers
+            .iter()
+            .position(|h| h == "date")
+            .with_context(|| format!("{} has no date column", filename.display()))?;
+        let mut rows = BTreeMap::new();
+        for row in reader.records() {
+            let row = row?;
+            rows.insert(row[date_col].to_owned(), row);
+        }
+        Ok((headers, rows))
+    };
+    let (old_headers, old_rows) = read(old_filename)?;
+    let (new_headers, new_rows) = read(new_filename)?;
+    let columns: Vec<(usize, usize, &str)> = new_headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| *h != "date")
+        .filter_map(|(new_idx, h)| Some((old_headers.iter().position(|o| o == h)?, new_idx, h)))
+        .collect();
+
+    println!("Differences from {} (old → new):", old_filename.display());
+    let mut num_dates = 0;
+    let dates: BTreeSet<&String> = old_rows.keys().chain(new_rows.keys()).collect();
+    for date in dates {
+        let diffs: Vec<String> = match (old_rows.get(date), new_rows.get(date)) {
+            (Some(old), Some(new)) => columns
+                .iter()
+                .filter(|(old_idx, new_idx, _)| old[*old_idx] != new[*new_idx])
+                .map(|(old_idx, new_idx, col)| {
+                    format!("{} {} → {}", col, &old[*old_idx], &new[*new_idx])
+                })
+                .collect(),
+            (Some(_), None) => vec!["only in the old file".to_string()],
+            (None, _) => vec!["only in the new file".to_string()],
+        };
+        if !diffs.is_empty() {
+            num_dates += 1;
+            println!("{}: {}", date, diffs.join(", "));
+        }
+    }
+    for col in new_headers
+        .iter()
+        .filter(|h| !old_headers.iter().any(|o| o == *h))
+    {
+        println!("Column {} is only in the new file", col);
+    }
+    for col in old_headers
+        .iter()
+        .filter(|h| !new_headers.iter().any(|n| n == *h))
+    {
+        println!("Column {} is only in the old file", col);
+    }
+    println!("{} dates differ", num_dates);
+    Ok(())
+}
+
+/// Replace any `strftime` tokens in the output prefix with this time.
+fn expand_output_prefix(prefix: &str, now: chrono::DateTime<chrono::Local>) -> Result<String> {
+    let items: Vec<_> = chrono::format::StrftimeItems::new(prefix).collect();
+    if items.contains(&chrono::format::Item::Error) {
+        anyhow::bail!("Invalid strftime format in --output-prefix {:?}", prefix);
+    }
+    Ok(now.format_with_items(items.into_iter()).to_string())
+}
+
+/// Create the directory of this (expanded) `--output-prefix`, if needed. If the prefix is an
+/// existing directory, without a separator at the end, one is added, so the files go inside it.
+fn output_prefix_dir(mut prefix: String) -> Result<String> {
+    let path = std::path::Path::new(&prefix);
+    if !prefix.ends_with(std::path::is_separator) && path.is_dir() {
+        prefix.push(std::path::MAIN_SEPARATOR);
+        return Ok(prefix);
+    }
+    let dir = if prefix.ends_with(std::path::is_separator) {
+        Some(path)
+    } else {
+        path.parent()
+    };
+    if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Can't create output directory {}", dir.display()))?;
+    }
+    Ok(prefix)
+}
+
+/// The geohash cell of this point, with `precision` characters, as its 5 bits per character.
+/// Sorting these sorts the geohashes too.
+fn geohash(lat: f64, lon: f64, precision: u8) -> u64 {
+    let (mut lat_range, mut lon_range) = ((-90., 90.), (-180., 180.));
+    let mut bits = 0;
+    for bit in 0..u32::from(precision) * 5 {
+        // The bits are longitude & latitude in turn, starting with longitude
+        let (range, value) = if bit % 2 == 0 {
+            (&mut lon_range, lon)
+        } else {
+            (&mut lat_range, lat)
+        };
+        let mid = (range.0 + range.1) / 2.;
+        bits <<= 1;
+        if value >= mid {
+            bits |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+    }
+    bits
+}
+
+/// The usual base 32 string of a `geohash`.
+fn geohash_string(bits: u64, precision: u8) -> String {
+    const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+    (0..precision)
+        .rev()
+        .map(|i| BASE32[(bits >> (i * 5)) as usize & 31] as char)
+        .collect()
+}
+
+/// Grow this bounding box (min lon, min lat, max lon, max lat) to include the other one.
+fn extend_bbox(bbox: &mut [f64; 4], other: [f64; 4]) {
+    bbox[0] = bbox[0].min(other[0]);
+    bbox[1] = bbox[1].min(other[1]);
+    bbox[2] = bbox[2].max(other[2]);
+    bbox[3] = bbox[3].max(other[3]);
+}
+
+/// Number of users who edited in the 90 days up to `day`, but not in the last 30 of them, i.e.
+/// who might be "slipping away".
+fn at_risk_users(day_edit_users: &DayEditUsers, day: NaiveDate) -> usize {
+    let recent: HashSet<&u32> = day_edit_users
+        .range(day - chrono::Days::new(29)..=day)
+        .flat_map(|(_, uids)| uids)
+        .collect();
+    day_edit_users
+        .range(day - chrono::Days::new(89)..day - chrono::Days::new(29))
+        .flat_map(|(_, uids)| uids)
+        .filter(|uid| !recent.contains(uid))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// The fewest users who made more than half of these edits, per user. 0 if there are no edits.
+fn bus_factor(user_edits: &HashMap<u32, u64>) -> usize {
+    let total: u64 = user_edits.values().sum();
+    let mut edits: Vec<u64> = user_edits.values().copied().collect();
+    edits.sort_unstable_by(|a, b| b.cmp(a));
+    let mut sum = 0;
+    for (num_users, num_edits) in edits.into_iter().enumerate() {
+        sum += num_edits;
+        if sum * 2 > total {
+            return num_users + 1;
+        }
+    }
+    0
+}
+
+/// The Gini coefficient & Theil index of these numbers of edits per user. 0 is equal, and higher
+/// is more unequal. Gini is at most 1, and Theil at most ln(number of users).
+fn inequality(user_edits: &HashMap<u32, u64>) -> (f64, f64) {
+    let mut edits: Vec<f64> = user_edits.values().map(|n| *n as f64).collect();
+    if edits.is_empty() {
+        return (0., 0.);
+    }
+    edits.sort_unstable_by(f64::total_cmp);
+    let n = edits.len() as f64;
+    let total: f64 = edits.iter().sum();
+    let mean = total / n;
+    let gini = edits
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (2. * (i + 1) as f64 - n - 1.) * x)
+        .sum::<f64>()
+        / (n * total);
+    let theil = edits
+        .iter()
+        .map(|x| x / mean * (x / mean).ln())
+        .sum::<f64>()
+        / n;
+    let round = |x: f64| (x * 10_000.).round() / 10_000.;
+    (round(gini), round(theil))
+}
+
+/// The autocorrelation of this series with itself `lag` places later, from -1 to 1. `None` if
+/// the series is too short, or constant.
+fn autocorrelation(series: &[f64], lag: usize) -> Option<f64> {
+    if series.len() <= lag + 1 {
+        return None;
+    }
+    let mean = series.iter().sum::<f64>() / series.len() as f64;
+    let variance: f64 = series.iter().map(|x| (x - mean).powi(2)).sum();
+    if variance == 0. {
+        return None;
+    }
+    let covariance: f64 = series
+        .iter()
+        .zip(&series[lag..])
+        .map(|(x, y)| (x - mean) * (y - mean))
+        .sum();
+    Some(covariance / variance)
+}
+
+/// This timestamp as an RFC 3339 UTC datetime, e.g. `2023-01-01T09:00:00Z`.
+fn rfc3339(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0).map_or(timestamp.to_string(), |dt| {
+        dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    })
+}
+
+/// The median of these numbers, or 0 if there are none.
+fn median(mut values: Vec<i64>) -> f64 {
+    if values.is_empty() {
+        return 0.;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) as f64 / 2.
+    } else {
+        values[mid] as f64
+    }
+}
+
+/// Fraction of the days from the first to the last of these days (inclusive) which are in the
+/// set, rounded to 2 decimal places. 1 for daily mapping (or only one day).
+fn density(days: &BTreeSet<&NaiveDate>) -> f64 {
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        return 0.;
+    };
+    let span_days = (**last - **first).num_days() + 1;
+    (days.len() as f64 * 100. / span_days as f64).round() / 100.
+}
+
+/// The `pattern` of these edit days: `burst`, `daily`, `weekly` or `sporadic`.
+fn edit_pattern(days: &BTreeSet<&NaiveDate>, args: &Args) -> &'static str {
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        return "sporadic";
+    };
+    let longest_gap_days = days
+        .iter()
+        .zip(days.iter().skip(1))
+        .map(|(a, b)| (**b - **a).num_days())
+        .max()
+        .unwrap_or(0);
+    if (**last - **first).num_days() < args.burst_max_span_days {
+        "burst"
+    } else if density(days) >= args.daily_min_density {
+        "daily"
+    } else if longest_gap_days <= args.weekly_max_gap_days {
+        "weekly"
+    } else {
+        "sporadic"
+    }
+}
+
+/// Which of the days from `start` to `end` (inclusive) are in `days`, as a bitset (one bit per
+/// day, starting with the most significant bit of the first byte), in base64.
+fn edit_day_bitmap(days: &BTreeSet<&NaiveDate>, start: NaiveDate, end: NaiveDate) -> String {
+    let num_days = (end - start).num_days() as usize + 1;
+    let mut bits = vec![0u8; num_days.div_ceil(8)];
+    for day in days.range::<&NaiveDate, _>(&start..=&end) {
+        let idx = (**day - start).num_days() as usize;
+        bits[idx / 8] |= 0x80 >> (idx % 8);
+    }
+    base64::engine::general_purpose::STANDARD.encode(bits)
+}
+
+/// Checks the invariants of the `user_totals_per_day.csv` rows, for `--validate`.
+#[derive(Default)]
+struct Validator {
+    last_alltime_contributors: u64,
+    num_violations: u64,
+}
+
+impl Validator {
+    /// Print everything that's wrong with this row.
+    fn check(&mut self, day_total: &DayTotal, alltime_contributors: u64) {
+        let mut violations = Vec::new();
+        if day_total.rolling_yr_total < day_total.num_users {
+            violations.push(format!(
+                "rolling_yr_total {} is less than num_users {}",
+                day_total.rolling_yr_total, day_total.num_users
+            ));
+        }
+        if day_total.users_ge42_days > day_total.rolling_yr_total {
+            violations.push(format!(
+                "users_ge42_days {} is more than rolling_yr_total {}",
+                day_total.users_ge42_days, day_total.rolling_yr_total
+            ));
+        }
+        if !(0. ..=100.).contains(&day_total.pct_of_alltime_contributors) {
+            violations.push(format!(
+                "pct_of_alltime_contributors {} isn't between 0 & 100",
+                day_total.pct_of_alltime_contributors
+            ));
+        }
+        if alltime_contributors < self.last_alltime_contributors {
+            violations.push(format!(
+                "the number of all time contributors went down from {} to {}",
+                self.last_alltime_contributors, alltime_contributors
+            ));
+        }
+        if let (Some(creators), Some(maintainers)) = (day_total.creators, day_total.maintainers) {
+            if creators + maintainers != day_total.rolling_yr_total {
+                violations.push(format!(
+                    "creators {} & maintainers {} don't add up to rolling_yr_total {}",
+                    creators, maintainers, day_total.rolling_yr_total
+                ));
+            }
+        }
+        if let Some(bus_factor) = day_total
+            .bus_factor
+            .filter(|bus_factor| *bus_factor > day_total.rolling_yr_total)
+        {
+            violations.push(format!(
+                "bus_factor {} is more than rolling_yr_total {}",
+                bus_factor, day_total.rolling_yr_total
+            ));
+        }
+        for violation in violations {
+            eprintln!("Invalid row on {}: {}", day_total.date, violation);
+            self.num_violations += 1;
+        }
+        self.last_alltime_contributors = alltime_contributors;
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.num_violations > 0 {
+            anyhow::bail!(
+                "user_totals_per_day.csv violates {} invariants (--validate)",
+                self.num_violations
+            );
+        }
+        println!("Validated user_totals_per_day.csv: no invariants are violated");
+        Ok(())
+    }
+}
+
+/// `num` as a percentage of `total`, rounded to 2 decimal places.
+fn percent(num: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.;
+    }
+    (num as f64 * 10_000. / total as f64).round() / 100.
+}
+
+/// For `--print-run-hash`, a 64 bit FNV-1a hash, which (unlike `DefaultHasher`) is the same in
+/// every build.
+fn run_hash(args: &Args) -> Result<u64> {
+    let mut data = format!("{}\n{:?}\n", env!("CARGO_PKG_VERSION"), args);
+    let mut input_paths = Vec::new();
+    for path in args.input_filename.iter() {
+        input_paths.extend(parts::part_paths(path)?);
+    }
+    let paths = input_paths
+        .iter()
+        .chain(args.object_ids.iter())
+        .chain(args.countries.iter())
+        .chain(args.events_input.iter())
+        .chain(args.regions.iter())
+        .chain(args.population.iter());
+    for path in paths {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("Can't read input file {}", path.display()))?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        data.push_str(&format!(
+            "{} {} {}\n",
+            path.display(),
+            metadata.len(),
+            mtime.as_nanos()
+        ));
+    }
+    Ok(data.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    }))
+}
+
+/// Hashes of the tags, and of the location (nodes), node ids (ways) or members (relations), of
+/// this object, for `--track-tag-changes`.
+fn tag_geometry_hashes(o: &impl OSMObj) -> (u64, u64) {
+    use std::hash::{Hash, Hasher};
+    let mut tags: Vec<_> = o.tags().collect();
+    tags.sort_unstable();
+    let mut tags_hasher = std::hash::DefaultHasher::new();
+    tags.hash(&mut tags_hasher);
+
+    let mut geometry_hasher = std::hash::DefaultHasher::new();
+    if let Some(node) = o.as_node() {
+        node.lat_lon_f64()
+            .map(|(lat, lon)| (lat.to_bits(), lon.to_bits()))
+            .hash(&mut geometry_hasher);
+    } else if let Some(way) = o.as_way() {
+        way.nodes().hash(&mut geometry_hasher);
+    } else if let Some(relation) = o.as_relation() {
+        for (member_type, id, role) in relation.members() {
+            (type_index(member_type), id, role).hash(&mut geometry_hasher);
+        }
+    }
+    (tags_hasher.finish(), geometry_hasher.finish())
+}
+
+/// 0, 1, 2 for nodes, ways & relations.
+fn type_index(object_type: OSMObjectType) -> usize {
+    match object_type {
+        OSMObjectType::Node => 0,
+        OSMObjectType::Way => 1,
+        OSMObjectType::Relation => 2,
+    }
+}
+
+/// The peak resident memory of this process (`VmHWM`), in kB. Only known on Linux.
+fn peak_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Optionally report how long each phase takes (`--timing`).
+struct Timer {
+    enabled: bool,
+    start: Instant,
+    last: Instant,
+}
+
+impl Timer {
+    fn new(enabled: bool) -> Self {
+        let now = Instant::now();
+        Timer {
+            enabled,
+            start: now,
+            last: now,
+        }
+    }
+
+    /// Print how long it's been since the last phase finished.
+    fn lap(&mut self, phase: &str) {
+        if self.enabled {
+            eprintln!("Timing: {}: {:.3?}", phase, self.last.elapsed());
+        }
+        self.last = Instant::now();
+    }
+
+    fn total(&self) {
+        if self.enabled {
+            eprintln!("Timing: total: {:.3?}", self.start.elapsed());
+        }
+    }
+}
+
+/// Which objects in the input file are counted.
+struct ObjFilter {
+    /// `--read-from`, as a timestamp
+    read_from: Option<i64>,
+    min_object_version: Option<u32>,
+    require_tags: bool,
+    object_ids: Option<ObjectIds>,
+    exclude_username: Option<Regex>,
+    /// `--first-n-days`, and every user's first edit day
+    first_n_days: Option<(chrono::Days, HashMap<u32, NaiveDate>)>,
+    /// From `--exclude-changesets-over` & `--exclude-largest-changeset`
+    excluded_changesets: HashSet<u32>,
+    /// `--min-lat`/`--max-lat`, and the nodes which were ever in that range
+    lat_range: Option<(std::ops::RangeInclusive<f64>, HashSet<ObjId>)>,
+    uid_shard: Option<(u32, u32)>,
+}
+
+impl ObjFilter {
+    fn new(args: &Args) -> Result<Self> {
+        Ok(ObjFilter {
+            read_from: args
+                .read_from
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()),
+            min_object_version: args.min_object_version,
+            require_tags: args.require_tags,
+            object_ids: args
+                .object_ids
+                .as_ref()
+                .map(ObjectIds::from_file)
+                .transpose()?,
+            exclude_username: args.exclude_bots_by_name.clone(),
+            first_n_days: None,
+            excluded_changesets: HashSet::new(),
+            lat_range: None,
+            uid_shard: args.uid_shard,
+        })
+    }
+
+    fn keep(&self, o: &impl OSMObj) -> bool {
+        if self
+            .uid_shard
+            .is_some_and(|(shard, num_shards)| obj_uid(o) % num_shards != shard)
+        {
+            return false;
+        }
+        if self
+            .read_from
+            .is_some_and(|read_from| obj_timestamp(o).is_some_and(|ts| ts < read_from))
+        {
+            return false;
+        }
+        if self
+            .min_object_version
+            .is_some_and(|min_version| o.version().unwrap_or(0) < min_version)
+        {
+            return false;
+        }
+        if self.require_tags && o.untagged() {
+            return false;
+        }
+        if self.object_ids.as_ref().is_some_and(|ids| !ids.contains(o)) {
+            return false;
+        }
+        if let (Some(regex), Some(username)) = (&self.exclude_username, o.user()) {
+            if regex.is_match(username) {
+                return false;
+            }
+        }
+        if o.changeset_id()
+            .is_some_and(|changeset_id| self.excluded_changesets.contains(&changeset_id))
+        {
+            return false;
+        }
+        if let Some((lat_range, nodes)) = &self.lat_range {
+            // Nodes by their own location, or, when deleted, where they were before
+            let in_range = if let Some(node) = o.as_node() {
+                node.lat_lon_f64()
+                    .map_or(nodes.contains(&o.id()), |(lat, _)| lat_range.contains(&lat))
+            } else if let Some(way) = o.as_way() {
+                way.nodes().iter().any(|id| nodes.contains(id))
+            } else if let Some(relation) = o.as_relation() {
+                relation.members().any(|(member_type, id, _)| {
+                    member_type == OSMObjectType::Node && nodes.contains(&id)
+                })
+            } else {
+                true
+            };
+            if !in_range {
+                return false;
+            }
+        }
+        if let Some((num_days, first_days)) = &self.first_n_days {
+            if o.uid()
+                .and_then(|uid| first_days.get(&canonical_uid(uid)))
+                .is_some_and(|first_day| {
+                    // So many days that it's past the last date there can be keeps everything
+                    first_day
+                        .checked_add_days(*num_days)
+                        .is_some_and(|end| obj_day(o).is_some_and(|day| day >= end))
+                })
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A set of object ids, per object type.
+#[derive(Default)]
+struct ObjectIds {
+    nodes: HashSet<ObjId>,
+    ways: HashSet<ObjId>,
+    relations: HashSet<ObjId>,
+}
+
+impl ObjectIds {
+    fn from_file(path: &PathBuf) -> Result<Self> {
+        let mut ids = ObjectIds::default();
+        let file = BufReader::new(File::open(path)?);
+        for (lineno, line) in file.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line.split_once('/').and_then(|(object_type, id)| {
+                let set = match object_type {
+                    "node" => &mut ids.nodes,
+                    "way" => &mut ids.ways,
+                    "relation" => &mut ids.relations,
+                    _ => return None,
+                };
+                set.insert(id.parse().ok()?);
+                Some(())
+            });
+            if parsed.is_none() {
+                anyhow::bail!(
+                    "{}:{}: expected an object like node/123, way/456 or relation/789, got {:?}",
+                    path.display(),
+                    lineno + 1,
+                    line
+                );
+            }
+        }
+        Ok(ids)
+    }
+
+    fn contains(&self, o: &impl OSMObj) -> bool {
+        match o.object_type() {
+            OSMObjectType::Node => &self.nodes,
+            OSMObjectType::Way => &self.ways,
+            OSMObjectType::Relation => &self.relations,
+        }
+        .contains(&o.id())
+    }
+}
+
+/// Write `path` with `write`. Like `OutputCsv`, it's written to `<path>.tmp`, and only renamed to
+/// `path` once it's complete.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut output = BufWriter::new(
+        File::create(&tmp_path).with_context(|| format!("Can't create {}", path.display()))?,
+    );
+    write(&mut output)?;
+    output.flush()?;
+    drop(output);
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A CSV output file, which is written to `<filename>.tmp`, and only renamed to `filename` once
+/// it's complete. Readers never see a half written file.
+struct OutputCsv {
+    filename: String,
+    writer: BufWriter<File>,
+    /// Each record is serialized into this first, so that one which fails isn't half written to
+    /// `writer`
+    row: csv::Writer<RowBuffer>,
+    /// Columns to rename in the header, from the field name in the record struct
+    renames: Vec<(&'static str, String)>,
+    header_written: bool,
+    /// With `--append`, the header of the existing file, which new rows must match
+    existing_header: Option<csv::StringRecord>,
+    /// With `--append`, the last date in the existing file
+    last_date: Option<NaiveDate>,
+}
+
+/// A CSV writer without a header row, as `OutputCsv` writes it itself.
+fn csv_writer<W: std::io::Write>(writer: W) -> csv::Writer<W> {
+    // Usernames & tag values can contain anything (newlines, quotes, commas…). Any field with
+    // those is quoted & escaped, so it can't break the row structure.
+    csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Necessary)
+        .has_headers(false)
+        .from_writer(writer)
+}
+
+impl OutputCsv {
+    fn create(filename: String) -> Result<Self> {
+        let writer = BufWriter::new(File::create(format!("{}.tmp", filename))?);
+        Ok(OutputCsv {
+            filename,
+            writer,
+            row: csv_writer(RowBuffer::default()),
+            renames: Vec::new(),
+            header_written: false,
+            existing_header: None,
+            last_date: None,
+        })
+    }
+
+    /// With `append`, and if the file already exists, add new rows after its existing ones.
+    /// Otherwise, like `create`.
+    fn open(filename: String, append: bool) -> Result<Self> {
+        if !append || !std::path::Path::new(&filename).exists() {
+            return Self::create(filename);
+        }
+        let mut reader = csv::Reader::from_path(&filename)
+            .with_context(|| format!("Can't read {} to append to it", filename))?;
+        let header = reader.headers()?.clone();
+        let date_col = header
+            .iter()
+            .position(|col| col == "date")
+            .with_context(|| format!("Can't append to {}, it has no date column", filename))?;
+        let mut last_date = None;
+        for row in reader.records() {
+            last_date = Some(
+                parse_date(&row?[date_col])
+                    .with_context(|| format!("Can't append to {}, invalid date", filename))?,
+            );
+        }
+
+        let tmp_filename = format!("{}.tmp", filename);
+        std::fs::copy(&filename, &tmp_filename)?;
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&tmp_filename)?;
+        let writer = BufWriter::new(file);
+        Ok(OutputCsv {
+            filename,
+            writer,
+            row: csv_writer(RowBuffer::default()),
+            renames: Vec::new(),
+            header_written: true,
+            existing_header: Some(header),
+            last_date,
+        })
+    }
+
+    /// Whether rows for this date should be written. With `--append`, only dates after the ones
+    /// already in the file are.
+    fn wants_date(&self, date: NaiveDate) -> bool {
+        self.last_date.is_none_or(|last_date| date > last_date)
+    }
+
+    /// Use `to` as the header for the `from` column.
+    fn rename_column(mut self, from: &'static str, to: String) -> Self {
+        self.renames.push((from, to));
+        self
+    }
+
+    /// Write one record, and the header before the first one. Unless `--strict`, a record which
+    /// can't be serialized is skipped, with a warning.
+    fn serialize(&mut self, record: impl serde::Serialize) -> Result<()> {
+        if let Err(err) = self.row.serialize(&record) {
+            if STRICT.load(Ordering::SeqCst) || !matches!(err.kind(), csv::ErrorKind::Serialize(_))
+            {
+                return Err(err.into());
+            }
+            eprintln!(
+                "Warning: Skipping a row of {} which can't be written: {}",
+                self.filename, err
+            );
+            WRITE_ERRORS.fetch_add(1, Ordering::SeqCst);
+            // It can have part of the failed record
+            self.row = csv_writer(RowBuffer::default());
+            return Ok(());
+        }
+        if !self.header_written || self.existing_header.is_some() {
+            // Let csv work out the column names from the record's fields
+            let mut header_writer = csv::Writer::from_writer(vec![]);
+            header_writer.serialize(&record)?;
+            let bytes = header_writer.into_inner()?;
+            let header: Vec<String> = csv::Reader::from_reader(bytes.as_slice())
+                .headers()?
+                .iter()
+                .map(|col| {
+                    self.renames
+                        .iter()
+                        .find(|(from, _)| *from == col)
+                        .map_or(col, |(_, to)| to.as_str())
+                        .to_owned()
+                })
+                .collect();
+            if let Some(existing_header) = self.existing_header.take() {
+                if existing_header.iter().ne(header.iter()) {
+                    anyhow::bail!(
+                        "Can't append to {}, it has different columns ({})",
+                        self.filename,
+                        existing_header.iter().collect::<Vec<_>>().join(",")
+                    );
+                }
+            } else {
+                let mut header_writer = csv_writer(vec![]);
+                header_writer.write_record(header)?;
+                self.writer.write_all(&header_writer.into_inner()?)?;
+            }
+            self.header_written = true;
+        }
+        self.write_row()
+    }
+
+    /// Write a record which isn't a struct, e.g. because the number of columns depends on the
+    /// data.
+    fn write_record<I>(&mut self, record: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.row.write_record(record)?;
+        self.write_row()
+    }
+
+    /// Move the row from `row` to the file.
+    fn write_row(&mut self) -> Result<()> {
+        self.row.flush()?;
+        let mut bytes = self.row.get_ref().0.borrow_mut();
+        self.writer.write_all(&bytes)?;
+        bytes.clear();
+        Ok(())
+    }
+
+    /// Flush everything & move the file into place.
+    fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        std::fs::rename(format!("{}.tmp", self.filename), &self.filename)?;
+        Ok(())
+    }
+}
+
+/// The bytes of the row being written. It's a `RefCell`, as `csv::Writer` only gives shared
+/// access to what it writes to.
+#[derive(Default)]
+struct RowBuffer(std::cell::RefCell<Vec<u8>>);
+
+impl Write for RowBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.get_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where `users_per_day` is written to, depending on `--format`.
+// There's only ever one of these, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum UsersPerDayOutput {
+    Csv(OutputCsv),
+    Arrow(ArrowUserDays),
+}
+
+impl UsersPerDayOutput {
+    fn serialize(&mut self, row: UserDay) -> Result<()> {
+        match self {
+            UsersPerDayOutput::Csv(output) => output.serialize(row),
+            UsersPerDayOutput::Arrow(output) => output.serialize(row),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            UsersPerDayOutput::Csv(output) => output.finish(),
+            UsersPerDayOutput::Arrow(output) => output.finish(),
+        }
+    }
+}
+
+/// The last day of the per-day stats, from `--end-date`, which is the day before with
+/// `--end-exclusive`.
+fn last_date(args: &Args) -> Option<NaiveDate> {
+    args.end_date.map(|end_date| {
+        if args.end_exclusive {
+            end_date.pred_opt().unwrap_or(NaiveDate::MIN)
+        } else {
+            end_date
+        }
+    })
+}
+
+/// The (inclusive) range of dates to write per-day stats for.
+///
+/// `start`/`end` default to, and are clamped to, the dates in the file. If that's fewer than
+/// `min_num_days` days, `start` is moved earlier so that `min_num_days` days are included, but
+/// never before the first day in the file. So if the file has fewer days, it's the whole file.
+fn output_date_range(
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    file_range: (NaiveDate, NaiveDate),
+    min_num_days: u32,
+) -> (NaiveDate, NaiveDate) {
+    let (file_start, file_end) = file_range;
+    let mut start = clamp(start.unwrap_or(file_start), file_start, file_end);
+    let end = clamp(end.unwrap_or(file_end), file_start, file_end);
+    if min_num_days > 0 {
+        // More days than the whole file (or than any date) is the whole file
+        let earliest_start = end
+            .checked_sub_days(chrono::Days::new(u64::from(min_num_days) - 1))
+            .map_or(file_start, |earliest_start| earliest_start.max(file_start));
+        start = start.min(earliest_start);
+    }
+    (start, end)
 }
 
 fn clamp<T: Ord>(val: T, min_val: T, max_val: T) -> T {
@@ -0,0 +1,339 @@
+//! A minimal reader for `.o5m` (& history `.o5h`) files, which `osmio` can't read.
+//!
+//! See <https://wiki.openstreetmap.org/wiki/O5m>. Only what this tool needs is supported: The
+//! objects (with their history), tags & author information. Bounding boxes & the file timestamp
+//! are skipped.
+use anyhow::{Context, Result};
+use osmio::obj_types::{StringNodeBuilder, StringOSMObj, StringRelationBuilder, StringWayBuilder};
+use osmio::{Lat, Lon, OSMObjectType, ObjId, TimestampFormat};
+use std::collections::VecDeque;
+use std::io::Read;
+
+/// The o5m files start with this.
+pub const MAGIC: &[u8] = b"\xff\xe0\x04o5";
+
+/// Strings are referred back to from this many strings later at most.
+const STRING_TABLE_SIZE: usize = 15_000;
+
+/// Longer strings (pairs) aren't put in the string table.
+const MAX_TABLE_STRING_LEN: usize = 250;
+
+/// Iterator over all objects in an o5m file.
+///
+/// Objects are read one at a time. If the file is malformed, iteration stops, and the error is
+/// returned by `finish`.
+pub struct O5mReader<R: Read> {
+    reader: R,
+    error: Option<anyhow::Error>,
+    finished: bool,
+    /// Each value is stored as the difference to the previous one
+    deltas: Deltas,
+    strings: VecDeque<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Deltas {
+    /// node, way & relation ids
+    ids: [i64; 3],
+    timestamp: i64,
+    changeset: i64,
+    lon: i64,
+    lat: i64,
+    way_node: i64,
+    /// Node, way & relation member ids
+    members: [i64; 3],
+}
+
+impl<R: Read> O5mReader<R> {
+    pub fn new(reader: R) -> Self {
+        O5mReader {
+            reader,
+            error: None,
+            finished: false,
+            deltas: Deltas::default(),
+            strings: VecDeque::new(),
+        }
+    }
+
+    /// Return the error which stopped the reading, if any.
+    pub fn finish(self) -> Result<()> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// The next object, or `None` at the end of the file.
+    fn next_object(&mut self) -> Result<Option<StringOSMObj>> {
+        loop {
+            let Some(dataset_type) = self.read_byte()? else {
+                return Ok(None);
+            };
+            match dataset_type {
+                // Reset
+                0xff => {
+                    self.deltas = Deltas::default();
+                    self.strings.clear();
+                    continue;
+                }
+                // End of file
+                0xfe => return Ok(None),
+                // Other datasets without a length
+                0xf0..=0xfd => continue,
+                _ => {}
+            }
+            let mut len_bytes = Vec::new();
+            loop {
+                let byte = self
+                    .read_byte()?
+                    .context("o5m file ends in a dataset length")?;
+                len_bytes.push(byte);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let len = uvarint(&mut len_bytes.as_slice())? as usize;
+            let mut data = vec![0; len];
+            self.reader
+                .read_exact(&mut data)
+                .context("o5m file ends in the middle of a dataset")?;
+            let mut data = data.as_slice();
+            let object = match dataset_type {
+                0x10 => self.read_node(&mut data)?,
+                0x11 => self.read_way(&mut data)?,
+                0x12 => self.read_relation(&mut data)?,
+                // Header, bounding box, file timestamp etc.
+                _ => continue,
+            };
+            return Ok(Some(object));
+        }
+    }
+
+    fn read_node(&mut self, data: &mut &[u8]) -> Result<StringOSMObj> {
+        let mut node = StringNodeBuilder::default();
+        node._id(delta(&mut self.deltas.ids[0], data)?);
+        let author = self.read_author(data)?;
+        if data.is_empty() {
+            node._deleted(true);
+        } else {
+            let lon = delta(&mut self.deltas.lon, data)? as i32;
+            let lat = delta(&mut self.deltas.lat, data)? as i32;
+            node._lat_lon((Lat::from_inner(lat), Lon::from_inner(lon)));
+            node._tags(self.read_tags(data)?);
+        }
+        let mut node = node.build().map_err(|e| anyhow::anyhow!("{}", e))?;
+        author.set(&mut node);
+        Ok(node.into())
+    }
+
+    fn read_way(&mut self, data: &mut &[u8]) -> Result<StringOSMObj> {
+        let mut way = StringWayBuilder::default();
+        way._id(delta(&mut self.deltas.ids[1], data)?);
+        let author = self.read_author(data)?;
+        if data.is_empty() {
+            way._deleted(true);
+        } else {
+            let refs_len = uvarint(data)? as usize;
+            let mut refs = data.get(..refs_len).context("o5m way refs are too long")?;
+            *data = &data[refs_len..];
+            let mut nodes = Vec::new();
+            while !refs.is_empty() {
+                nodes.push(delta(&mut self.deltas.way_node, &mut refs)?);
+            }
+            way._nodes(nodes);
+            way._tags(self.read_tags(data)?);
+        }
+        let mut way = way.build().map_err(|e| anyhow::anyhow!("{}", e))?;
+        author.set(&mut way);
+        Ok(way.into())
+    }
+
+    fn read_relation(&mut self, data: &mut &[u8]) -> Result<StringOSMObj> {
+        let mut relation = StringRelationBuilder::default();
+        relation._id(delta(&mut self.deltas.ids[2], data)?);
+        let author = self.read_author(data)?;
+        if data.is_empty() {
+            relation._deleted(true);
+        } else {
+            let refs_len = uvarint(data)? as usize;
+            let mut refs = data
+                .get(..refs_len)
+                .context("o5m relation refs are too long")?;
+            *data = &data[refs_len..];
+            let mut members = Vec::new();
+            while !refs.is_empty() {
+                let id_delta = svarint(&mut refs)?;
+                let type_role = self.read_string(&mut refs, false)?;
+                let (member_type, idx) = match type_role.first() {
+                    Some(b'0') => (OSMObjectType::Node, 0),
+                    Some(b'1') => (OSMObjectType::Way, 1),
+                    Some(b'2') => (OSMObjectType::Relation, 2),
+                    _ => anyhow::bail!("Unknown o5m relation member type"),
+                };
+                self.deltas.members[idx] += id_delta;
+                let role = String::from_utf8_lossy(&type_role[1..]).into_owned();
+                members.push((member_type, self.deltas.members[idx], role));
+            }
+            relation._members(members);
+            relation._tags(self.read_tags(data)?);
+        }
+        let mut relation = relation.build().map_err(|e| anyhow::anyhow!("{}", e))?;
+        author.set(&mut relation);
+        Ok(relation.into())
+    }
+
+    /// The version, timestamp, changeset & user of an object.
+    fn read_author(&mut self, data: &mut &[u8]) -> Result<Author> {
+        let version = uvarint(data)? as u32;
+        if version == 0 {
+            return Ok(Author::default());
+        }
+        let mut author = Author {
+            version: Some(version),
+            ..Default::default()
+        };
+        self.deltas.timestamp += svarint(data)?;
+        if self.deltas.timestamp == 0 {
+            return Ok(author);
+        }
+        author.timestamp = Some(self.deltas.timestamp);
+        author.changeset = Some(delta(&mut self.deltas.changeset, data)? as u32);
+        let uid_user = self.read_string(data, true)?;
+        let sep = uid_user
+            .iter()
+            .position(|b| *b == 0)
+            .context("o5m uid/user pair without separator")?;
+        // Anonymous edits have an empty uid
+        author.uid = Some(if sep == 0 {
+            0
+        } else {
+            uvarint(&mut &uid_user[..sep])? as u32
+        });
+        author.user = Some(String::from_utf8_lossy(&uid_user[sep + 1..]).into_owned());
+        Ok(author)
+    }
+
+    fn read_tags(&mut self, data: &mut &[u8]) -> Result<Vec<(String, String)>> {
+        let mut tags = Vec::new();
+        while !data.is_empty() {
+            let pair = self.read_string(data, true)?;
+            let sep = pair
+                .iter()
+                .position(|b| *b == 0)
+                .context("o5m tag without separator")?;
+            tags.push((
+                String::from_utf8_lossy(&pair[..sep]).into_owned(),
+                String::from_utf8_lossy(&pair[sep + 1..]).into_owned(),
+            ));
+        }
+        Ok(tags)
+    }
+
+    /// A string (or with `pair`, 2 strings, returned with a 0 byte between them), either inline,
+    /// or a reference to an earlier one.
+    fn read_string(&mut self, data: &mut &[u8], pair: bool) -> Result<Vec<u8>> {
+        let reference = uvarint(data)? as usize;
+        if reference != 0 {
+            return self
+                .strings
+                .get(reference - 1)
+                .cloned()
+                .context("o5m string reference to an unknown string");
+        }
+        let mut end = data
+            .iter()
+            .position(|b| *b == 0)
+            .context("o5m string without end")?;
+        if pair {
+            end += 1 + data[end + 1..]
+                .iter()
+                .position(|b| *b == 0)
+                .context("o5m string pair without end")?;
+        }
+        let string = data[..end].to_vec();
+        *data = &data[end + 1..];
+        if string.len() - usize::from(pair) <= MAX_TABLE_STRING_LEN {
+            self.strings.push_front(string.clone());
+            self.strings.truncate(STRING_TABLE_SIZE);
+        }
+        Ok(string)
+    }
+}
+
+impl<R: Read> Iterator for O5mReader<R> {
+    type Item = StringOSMObj;
+
+    fn next(&mut self) -> Option<StringOSMObj> {
+        if self.finished {
+            return None;
+        }
+        match self.next_object() {
+            Ok(Some(object)) => Some(object),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Author {
+    version: Option<u32>,
+    timestamp: Option<i64>,
+    changeset: Option<u32>,
+    uid: Option<u32>,
+    user: Option<String>,
+}
+
+impl Author {
+    fn set(self, o: &mut impl osmio::OSMObjBase) {
+        o.set_version(self.version);
+        o.set_timestamp(self.timestamp.map(TimestampFormat::EpochNunber));
+        o.set_changeset_id(self.changeset);
+        o.set_uid(self.uid);
+        o.set_user(self.user.as_deref());
+    }
+}
+
+fn uvarint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first().context("o5m number is cut off")?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("o5m number is too long")
+}
+
+/// Signed numbers have the sign in the lowest bit.
+fn svarint(data: &mut &[u8]) -> Result<i64> {
+    let value = uvarint(data)?;
+    Ok(if value & 1 == 0 {
+        (value >> 1) as i64
+    } else {
+        -((value >> 1) as i64) - 1
+    })
+}
+
+/// Read a delta encoded value.
+fn delta(last: &mut i64, data: &mut &[u8]) -> Result<ObjId> {
+    *last += svarint(data)?;
+    Ok(*last)
+}
@@ -0,0 +1,128 @@
+//! Input files which are split into several part files (e.g. `planet.osh.pbf.part0`,
+//! `planet.osh.pbf.part1`, …), given as `planet.osh.pbf.part*`. The parts are read one after
+//! the other, as if they'd been `cat`ed together, without needing the disk space for that.
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// The files which make up `path`, in order. If the file name ends with `*`, that's all the
+/// files in that directory which start with the rest of the name, followed by a number, in order
+/// of that number. The numbers can start at 0 or 1, but none can be missing. Otherwise it's only
+/// `path`.
+pub fn part_paths(path: &Path) -> Result<Vec<PathBuf>> {
+    let Some(prefix) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix('*'))
+    else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let mut parts = Vec::new();
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("Can't read the directory of {}", path.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(num) = name.to_str().and_then(|name| name.strip_prefix(prefix)) else {
+            continue;
+        };
+        let num: u64 = num.parse().with_context(|| {
+            format!(
+                "{} matches {}, but isn't a numbered part",
+                entry.path().display(),
+                path.display()
+            )
+        })?;
+        parts.push((num, path.with_file_name(name)));
+    }
+    parts.sort();
+    if parts.is_empty() {
+        anyhow::bail!("There are no part files {}", path.display());
+    }
+    for (expected, (num, part_path)) in (parts[0].0.min(1)..).zip(parts.iter()) {
+        if *num != expected {
+            anyhow::bail!(
+                "Part {} of {} is missing, the next part is {}",
+                expected,
+                path.display(),
+                part_path.display()
+            );
+        }
+    }
+    Ok(parts.into_iter().map(|(_, part_path)| part_path).collect())
+}
+
+/// The total size of all the parts of `path`.
+pub fn len(path: &Path) -> Result<u64> {
+    let mut len = 0;
+    for part_path in part_paths(path)? {
+        len += std::fs::metadata(&part_path)
+            .with_context(|| format!("Can't read input file {}", part_path.display()))?
+            .len();
+    }
+    Ok(len)
+}
+
+/// Reads all the parts of a file, one after the other.
+pub struct Parts {
+    /// The parts after the current one, last first.
+    next_paths: Vec<PathBuf>,
+    file: File,
+}
+
+impl Parts {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut next_paths = part_paths(path)?;
+        next_paths.reverse();
+        let first_path = next_paths.pop().unwrap();
+        Ok(Parts {
+            file: open_part(&first_path)?,
+            next_paths,
+        })
+    }
+
+    /// Skip the next `len` bytes, which can be in later parts, without reading them. Fails if
+    /// there aren't that many.
+    pub fn skip(&mut self, mut len: u64) -> std::io::Result<()> {
+        loop {
+            let left = self.file.metadata()?.len() - self.file.stream_position()?;
+            if len <= left {
+                self.file.seek_relative(len as i64)?;
+                return Ok(());
+            }
+            len -= left;
+            if !self.next_part()? {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+
+    /// Start reading the next part. `false` if that was the last one.
+    fn next_part(&mut self) -> std::io::Result<bool> {
+        let Some(path) = self.next_paths.pop() else {
+            return Ok(false);
+        };
+        self.file = open_part(&path).map_err(std::io::Error::other)?;
+        Ok(true)
+    }
+}
+
+impl Read for Parts {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let num_read = self.file.read(buf)?;
+            if num_read > 0 || buf.is_empty() || !self.next_part()? {
+                return Ok(num_read);
+            }
+        }
+    }
+}
+
+fn open_part(path: &Path) -> Result<File> {
+    File::open(path).with_context(|| format!("Can't read input file {}", path.display()))
+}
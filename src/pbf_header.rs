@@ -0,0 +1,150 @@
+//! Reading the header block of a PBF file, which `osmio` doesn't expose.
+//!
+//! Only the few protobuf fields needed are decoded, see
+//! <https://wiki.openstreetmap.org/wiki/PBF_Format>.
+use crate::parts::{self, Parts};
+use anyhow::Result;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// The `osmosis_replication_timestamp` (seconds since the epoch) in the header of this PBF file.
+/// `None` if there isn't one, or if the header can't be understood.
+pub fn replication_timestamp(path: &Path) -> Result<Option<i64>> {
+    let mut file = BufReader::new(Parts::open(path)?);
+    Ok(read_header_block(&mut file).and_then(|header_block| {
+        fields(&header_block)?
+            .into_iter()
+            .find_map(|(field, value)| match (field, value) {
+                (32, Value::Varint(ts)) => Some(ts as i64),
+                _ => None,
+            })
+    }))
+}
+
+/// Check that none of the blobs of this PBF file are cut off (e.g. by an incomplete download),
+/// by reading only their headers. `osmio` panics on these. Returns what's wrong, and where, if
+/// anything is. For a file in parts, this checks that they fit together.
+pub fn check_blobs(path: &Path) -> Result<Option<String>> {
+    let file_len = parts::len(path)?;
+    let mut file = Parts::open(path)?;
+    let mut offset = 0;
+    let mut blob_num = 0;
+    while offset < file_len {
+        blob_num += 1;
+        let mut len = [0; 4];
+        if file.read_exact(&mut len).is_err() {
+            return Ok(Some(format!(
+                "blob {} at byte {} is cut off",
+                blob_num, offset
+            )));
+        }
+        let mut blob_header = vec![0; u32::from_be_bytes(len) as usize];
+        if offset + 4 + blob_header.len() as u64 > file_len {
+            return Ok(Some(format!(
+                "blob {} at byte {} is cut off",
+                blob_num, offset
+            )));
+        }
+        file.read_exact(&mut blob_header)?;
+        let Some(blob_len) = fields(&blob_header).and_then(|fields| {
+            fields
+                .into_iter()
+                .find_map(|(field, value)| match (field, value) {
+                    (3, Value::Varint(len)) => Some(len),
+                    _ => None,
+                })
+        }) else {
+            return Ok(Some(format!(
+                "blob {} at byte {} has an invalid header",
+                blob_num, offset
+            )));
+        };
+        let next_offset = offset + 4 + blob_header.len() as u64 + blob_len;
+        if next_offset > file_len {
+            return Ok(Some(format!(
+                "blob {} at byte {} is cut off",
+                blob_num, offset
+            )));
+        }
+        file.skip(blob_len)?;
+        offset = next_offset;
+    }
+    Ok(None)
+}
+
+/// The (uncompressed) HeaderBlock, i.e. the data of the first blob.
+fn read_header_block(file: &mut impl Read) -> Option<Vec<u8>> {
+    let mut len = [0; 4];
+    file.read_exact(&mut len).ok()?;
+    let mut blob_header = vec![0; u32::from_be_bytes(len) as usize];
+    file.read_exact(&mut blob_header).ok()?;
+    let blob_len = fields(&blob_header)?
+        .into_iter()
+        .find_map(|(field, value)| match (field, value) {
+            (3, Value::Varint(len)) => Some(len),
+            _ => None,
+        })?;
+    let mut blob = vec![0; blob_len as usize];
+    file.read_exact(&mut blob).ok()?;
+
+    for (field, value) in fields(&blob)? {
+        match (field, value) {
+            (1, Value::Bytes(raw)) => return Some(raw.to_vec()),
+            (3, Value::Bytes(zlib_data)) => {
+                let mut data = Vec::new();
+                flate2::read::ZlibDecoder::new(zlib_data)
+                    .read_to_end(&mut data)
+                    .ok()?;
+                return Some(data);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// All the fields of a protobuf message. Fixed size fields are skipped.
+fn fields(mut buf: &[u8]) -> Option<Vec<(u64, Value<'_>)>> {
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = varint(&mut buf)?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(varint(&mut buf)?),
+            1 => {
+                buf = buf.get(8..)?;
+                continue;
+            }
+            2 => {
+                let len = varint(&mut buf)? as usize;
+                let (bytes, rest) = (buf.get(..len)?, buf.get(len..)?);
+                buf = rest;
+                Value::Bytes(bytes)
+            }
+            5 => {
+                buf = buf.get(4..)?;
+                continue;
+            }
+            _ => return None,
+        };
+        fields.push((key >> 3, value));
+    }
+    Some(fields)
+}
+
+fn varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
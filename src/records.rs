@@ -0,0 +1,476 @@
+//! The rows of each output file.
+//!
+//! Every output writer serializes these structs, so the column names, order & types are only
+//! defined once.
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize, Serializer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// `--date-format`: How the `date` columns are written. ISO dates if it's not set.
+pub static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+/// `--excel-safe`: Write uids, and usernames which Excel would change, as Excel text formulas.
+pub static EXCEL_SAFE: AtomicBool = AtomicBool::new(false);
+
+/// One row of `user_totals_per_day.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DayTotal {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_users: usize,
+    pub rolling_yr_total: usize,
+    pub users_ge42_days: usize,
+    pub pct_of_alltime_contributors: f64,
+    /// Median number of days since the first edit, of the users in the window
+    pub median_account_age_days: f64,
+    /// Users who edited in the last 90 days, but not in the last 30
+    pub at_risk_users: usize,
+    /// Only with `--bus-factor`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus_factor: Option<usize>,
+    /// Only with `--inequality-metrics`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gini: Option<f64>,
+    /// Only with `--inequality-metrics`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theil: Option<f64>,
+    /// Only with `--normalize`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users_per_1k_edits: Option<f64>,
+    /// Only with `--sticky-threshold`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky_new_users: Option<usize>,
+    /// Only with `--creators`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creators: Option<usize>,
+    /// Only with `--creators`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintainers: Option<usize>,
+    /// Only with `--type-mix`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_only_users: Option<usize>,
+    /// Only with `--type-mix`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub way_relation_only_users: Option<usize>,
+    /// Only with `--type-mix`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mixed_type_users: Option<usize>,
+    /// Only with `--baseline-date`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rolling_yr_index: Option<f64>,
+    /// Only with `--flag-outlier-days`
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "opt_yes_no")]
+    pub is_outlier: Option<bool>,
+}
+
+/// One row of `user_totals_per_day.csv` with `--no-rolling`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DayNumUsers {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_users: usize,
+}
+
+/// One row of `users_per_day.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UserDay<'a> {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    #[serde(serialize_with = "excel_uid")]
+    pub uid: u32,
+    pub num_edit_days_last_yr: usize,
+    #[serde(serialize_with = "excel_username")]
+    pub username: &'a str,
+    #[serde(serialize_with = "yes_no")]
+    pub ge42days: bool,
+    pub mapped_days: String,
+    #[serde(serialize_with = "yes_no")]
+    pub likely_mechanical: bool,
+    pub account_age_days: i64,
+    pub density: f64,
+    pub pattern: &'a str,
+    /// Only with `--emit-bitmap`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit_day_bitmap: Option<String>,
+    /// Only with `--countries`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countries_edited: Option<usize>,
+    /// Only with `--precise-timestamps`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_edit_ts: Option<String>,
+    /// Only with `--precise-timestamps`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_edit_ts: Option<String>,
+    /// Only with `--track-restorations`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restorations: Option<u64>,
+    /// Only with `--track-objects`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub objects_touched: Option<usize>,
+    /// Only with `--track-tag-keys`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_tag_keys: Option<usize>,
+}
+
+/// One row of `net_flow.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct NetFlow {
+    #[serde(serialize_with = "formatted_date")]
+    pub period: NaiveDate,
+    pub new: u64,
+    pub returning: u64,
+    pub churned: u64,
+    pub net: i64,
+}
+
+/// One row of `resampled.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Resampled {
+    #[serde(serialize_with = "formatted_date")]
+    pub period: NaiveDate,
+    pub num_users: usize,
+    pub rolling_yr_total: usize,
+}
+
+/// One row of `anonymous_per_day.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AnonymousDay {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub registered_edits: u64,
+    pub anonymous_edits: u64,
+    pub pct_anonymous: f64,
+}
+
+/// One row of `editor_usage.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EditorUsage<'a> {
+    pub editor: &'a str,
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_users: usize,
+}
+
+/// One row of `type_activity.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TypeActivity {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub node_edits: u64,
+    pub way_edits: u64,
+    pub relation_edits: u64,
+}
+
+/// One row of `acquisition.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Acquisition {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub new_contributors: u64,
+    pub cumulative: u64,
+}
+
+/// One row of `isoweek.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct IsoWeek {
+    pub iso_year: i32,
+    pub iso_week: u32,
+    pub active_users: usize,
+    pub new_users: u64,
+}
+
+/// One line of the `--profiles` file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UserProfile<'a> {
+    pub uid: u32,
+    pub username: &'a str,
+    pub first_edit: NaiveDate,
+    pub last_edit: NaiveDate,
+    /// The time of the latest edit, with or without a username, even if it's on a day which
+    /// doesn't count (e.g. with `--min-edits-per-day`)
+    pub last_edit_ts: String,
+    pub num_edit_days: usize,
+    /// Most consecutive days with edits
+    pub longest_streak_days: usize,
+    /// `None` with only one edit day
+    pub longest_gap_days: Option<i64>,
+    /// Number of calendar years with edits
+    pub years_active: usize,
+    /// `years_active` divided by the number of calendar years from the first to the last edit
+    pub consistency: f64,
+    pub num_edits: u64,
+    pub num_changesets: usize,
+    /// min lon, min lat, max lon, max lat. `None` without any node edits
+    pub bbox: Option<[f64; 4]>,
+    /// Only with `--weight-by`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_edits: Option<u64>,
+}
+
+/// One row of `seasonality.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Seasonality {
+    pub month: u32,
+    pub num_days: usize,
+    pub mean_daily_users: Option<f64>,
+    pub seasonality_index: Option<f64>,
+}
+
+/// One row of `survival.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Survival {
+    pub months_since_first_edit: usize,
+    pub at_risk: usize,
+    pub survival: f64,
+}
+
+/// One target of the `--grafana` file. Datapoints are `[value, timestamp_ms]`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GrafanaTarget {
+    pub target: &'static str,
+    pub datapoints: Vec<(usize, i64)>,
+}
+
+/// One row of `geohash_counts.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GeohashCount {
+    pub geohash: String,
+    pub distinct_users: usize,
+    pub edit_count: u64,
+}
+
+/// One row of `newcomer_activity.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct NewcomerActivity {
+    pub days_since_first: usize,
+    pub num_users: usize,
+    pub active_fraction: f64,
+}
+
+/// One row of `year_overlap.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct YearOverlap {
+    pub year: i32,
+    pub carried_over: usize,
+    pub new: usize,
+    pub lost: usize,
+}
+
+/// One row of `cohort_quality.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CohortQuality {
+    pub month: String,
+    pub cohort_size: u64,
+    pub returned: u64,
+    pub pct_returned: f64,
+}
+
+/// One row of `hour_histogram.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HourHistogram {
+    pub hour: usize,
+    pub num_edits: u64,
+}
+
+/// One row of `experience_bands.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExperienceBands {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub tenure_0_1y: u64,
+    pub tenure_1_3y: u64,
+    pub tenure_3_5y: u64,
+    pub tenure_5y_plus: u64,
+}
+
+/// One row of `window_distribution.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WindowDistribution {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_edit_days: usize,
+    pub num_users: u64,
+}
+
+/// One row of `tier_transitions.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TierTransitions {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub casual: usize,
+    pub regular: usize,
+    pub core: usize,
+    /// Users in a higher tier than at the end of the month before, including new ones
+    pub moved_up: usize,
+    /// Users in a lower tier, including ones who aren't in the window anymore
+    pub moved_down: usize,
+}
+
+/// One row of `inter_arrival.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InterArrival<'a> {
+    #[serde(serialize_with = "excel_uid")]
+    pub uid: u32,
+    #[serde(serialize_with = "excel_username")]
+    pub username: &'a str,
+    pub num_edit_days: usize,
+    pub mean_gap_days: Option<f64>,
+    pub median_gap_days: Option<f64>,
+    pub max_gap_days: Option<i64>,
+    pub gaps: String,
+}
+
+/// One row of `edit_events.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EditEvent {
+    #[serde(serialize_with = "excel_uid")]
+    pub uid: u32,
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+}
+
+/// One line of the `--emit-events-jsonl` file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EditEventJson {
+    pub uid: u32,
+    pub date: NaiveDate,
+    pub num_edits: u64,
+}
+
+/// One row of `region_timeseries.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RegionTimeseries<'a> {
+    pub region: &'a str,
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub active_users: usize,
+    pub rolling_yr_total: usize,
+    /// Only with `--population`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_per_100k: Option<f64>,
+}
+
+/// One row of a `--user-calendars` file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CalendarDay {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_edits: u64,
+}
+
+/// One row of `tag_changes.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TagChanges<'a> {
+    #[serde(serialize_with = "excel_uid")]
+    pub uid: u32,
+    #[serde(serialize_with = "excel_username")]
+    pub username: &'a str,
+    pub tag_edits: u64,
+    pub geometry_edits: u64,
+}
+
+/// One row of `reactivation.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reactivation<'a> {
+    #[serde(serialize_with = "excel_uid")]
+    pub uid: u32,
+    #[serde(serialize_with = "excel_username")]
+    pub username: &'a str,
+    #[serde(serialize_with = "formatted_date")]
+    pub last_edit_before: NaiveDate,
+    #[serde(serialize_with = "formatted_date")]
+    pub return_date: NaiveDate,
+    pub gap_days: i64,
+}
+
+/// One row of `metrics_long.csv`, one daily metric on one date.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MetricLong<'a> {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub metric: &'a str,
+    pub value: MetricValue,
+}
+
+/// Metrics are mostly counts, but some are percentages.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Count(u64),
+    Decimal(f64),
+}
+
+/// One row of `user_<uid>.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UserDetail {
+    #[serde(serialize_with = "formatted_date")]
+    pub date: NaiveDate,
+    pub num_edits: u64,
+    pub num_changesets: usize,
+    pub node_edits: u64,
+    pub way_edits: u64,
+    pub relation_edits: u64,
+}
+
+/// One row of `co_edit_graph.csv`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CoEdit {
+    #[serde(serialize_with = "excel_uid")]
+    pub uid_a: u32,
+    #[serde(serialize_with = "excel_uid")]
+    pub uid_b: u32,
+    pub shared_objects: u64,
+}
+
+/// With `--excel-safe`, `="123"`, which Excel shows as the text `123`, rather than a number it
+/// can round or reformat.
+fn excel_uid<S: Serializer>(uid: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    if EXCEL_SAFE.load(Ordering::Relaxed) {
+        serializer.serialize_str(&format!("=\"{}\"", uid))
+    } else {
+        serializer.serialize_u32(*uid)
+    }
+}
+
+/// With `--excel-safe`, usernames which look like a number (e.g. `007`), or a formula (e.g.
+/// `=1+1`), are written like `excel_uid`.
+fn excel_username<S: Serializer>(username: &&str, serializer: S) -> Result<S::Ok, S::Error> {
+    let risky =
+        username.trim().parse::<f64>().is_ok() || username.starts_with(['=', '+', '-', '@']);
+    if EXCEL_SAFE.load(Ordering::Relaxed) && risky {
+        serializer.serialize_str(&format!("=\"{}\"", username.replace('"', "\"\"")))
+    } else {
+        serializer.serialize_str(username)
+    }
+}
+
+/// A date in the `--date-format`.
+pub fn format_date(date: &NaiveDate) -> String {
+    match DATE_FORMAT.get() {
+        Some(date_format) => date.format(date_format).to_string(),
+        None => date.to_string(),
+    }
+}
+
+/// Parse a date written by `format_date`.
+pub fn parse_date(s: &str) -> chrono::ParseResult<NaiveDate> {
+    match DATE_FORMAT.get() {
+        Some(date_format) => NaiveDate::parse_from_str(s, date_format),
+        None => s.parse(),
+    }
+}
+
+fn formatted_date<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_date(date))
+}
+
+/// Booleans are written as `yes`/`no`, like OSM tags.
+fn yes_no<S: Serializer>(val: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(if *val { "yes" } else { "no" })
+}
+
+/// Optional columns are skipped when they're `None`.
+fn opt_yes_no<S: Serializer>(val: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+    yes_no(&val.unwrap_or_default(), serializer)
+}
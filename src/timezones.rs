@@ -0,0 +1,39 @@
+//! `--timezone-per-region`: the UTC offset of the time zone each node is in.
+use crate::countries::Countries;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// The polygons of each time zone, and its UTC offset.
+pub struct TimeZones {
+    zones: Countries,
+    /// Seconds to add to UTC, for each polygon feature of `zones`
+    offsets: Vec<i64>,
+}
+
+impl TimeZones {
+    /// Read a GeoJSON `FeatureCollection`, with one `Polygon` or `MultiPolygon` feature per time
+    /// zone, each with a `zone` property, the UTC offset in hours (e.g. `-5` or `5.5`), like
+    /// Natural Earth's time zones.
+    pub fn from_geojson(path: &Path) -> Result<Self> {
+        let zones = Countries::from_geojson(path)?;
+        let offsets = (0..zones.len())
+            .map(|idx| {
+                let hours = zones.properties(idx)["zone"].as_f64().with_context(|| {
+                    format!(
+                        "Time zone without a numeric zone property in {}",
+                        path.display()
+                    )
+                })?;
+                Ok((hours * 3600.).round() as i64)
+            })
+            .collect::<Result<_>>()?;
+        Ok(TimeZones { zones, offsets })
+    }
+
+    /// The UTC offset, in seconds, of the (first) time zone this point is in.
+    pub fn offset(&self, lon: f64, lat: f64) -> Option<i64> {
+        self.zones
+            .find(lon, lat)
+            .map(|idx| self.offsets[usize::from(idx)])
+    }
+}
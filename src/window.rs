@@ -0,0 +1,57 @@
+//! The counts of each user in a rolling window, from the per-day data of the days in it (e.g.
+//! `day_edit_users.range(window)`).
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+
+/// Everyone who edited on these days, from the users per day.
+pub fn window_users<'a>(
+    day_edit_users: impl Iterator<Item = (&'a NaiveDate, &'a HashSet<u32>)>,
+) -> HashSet<u32> {
+    day_edit_users.flat_map(|(_, uids)| uids).copied().collect()
+}
+
+/// The days each user edited on, of these days, from the users per day.
+pub fn window_user_days<'a>(
+    day_edit_users: impl Iterator<Item = (&'a NaiveDate, &'a HashSet<u32>)>,
+) -> HashMap<u32, HashSet<&'a NaiveDate>> {
+    let mut user_days: HashMap<u32, HashSet<&NaiveDate>> = HashMap::new();
+    for (day, uids) in day_edit_users {
+        for uid in uids {
+            user_days.entry(*uid).or_default().insert(day);
+        }
+    }
+    user_days
+}
+
+/// The total number of edits of each user on these days, from the edits per day & user.
+pub fn window_user_edits<'a>(
+    day_user_edits: impl Iterator<Item = (&'a NaiveDate, &'a HashMap<u32, u64>)>,
+) -> HashMap<u32, u64> {
+    let mut user_edits: HashMap<u32, u64> = HashMap::new();
+    for (_, edits) in day_user_edits {
+        for (uid, num_edits) in edits {
+            *user_edits.entry(*uid).or_default() += num_edits;
+        }
+    }
+    user_edits
+}
+
+/// The number of different changesets of each user on these days, from the changesets per day &
+/// user.
+pub fn window_user_changesets<'a>(
+    day_user_changesets: impl Iterator<Item = (&'a NaiveDate, &'a HashMap<u32, HashSet<u32>>)>,
+) -> HashMap<u32, u64> {
+    let mut user_changesets: HashMap<u32, HashSet<u32>> = HashMap::new();
+    for (_, changesets) in day_user_changesets {
+        for (uid, these_changesets) in changesets {
+            user_changesets
+                .entry(*uid)
+                .or_default()
+                .extend(these_changesets);
+        }
+    }
+    user_changesets
+        .into_iter()
+        .map(|(uid, changesets)| (uid, changesets.len() as u64))
+        .collect()
+}
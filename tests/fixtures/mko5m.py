@@ -0,0 +1,78 @@
+#!/usr/bin/env python3
+"""Tiny OSM history o5m writer. Usage: mko5m.py in.json out.o5m
+
+in.json is the same as for mkpbf.py"""
+import json, sys, datetime
+
+def varint(n):
+    out = b""
+    while True:
+        b = n & 0x7F
+        n >>= 7
+        if n:
+            out += bytes([b | 0x80])
+        else:
+            return out + bytes([b])
+
+def svarint(n):
+    return varint((n << 1) if n >= 0 else ((-n - 1) << 1) | 1)
+
+def ts(s):
+    return int(datetime.datetime.fromisoformat(s.replace("Z", "+00:00")).timestamp())
+
+def main():
+    objs = json.load(open(sys.argv[1]))
+    if isinstance(objs, dict):
+        objs = objs["objects"]
+    out = open(sys.argv[2], "wb")
+    out.write(b"\xff\xe0\x04o5h")
+    # The string table, newest first
+    table = []
+    def string(*parts):
+        s = b"\x00".join(parts) + b"\x00"
+        if s in table:
+            return varint(table.index(s) + 1)
+        table.insert(0, s)
+        return b"\x00" + s
+    last = {}
+    def delta(name, n):
+        d = n - last.get(name, 0)
+        last[name] = n
+        return svarint(d)
+    last_type = None
+    for o in objs:
+        typ = o["type"]
+        if typ != last_type:
+            # Reset at each new object type, like osmconvert does
+            out.write(b"\xff")
+            table.clear()
+            last.clear()
+            last_type = typ
+        data = delta("id", o["id"])
+        if o.get("redacted"):
+            # A timestamp of 0 means there's no author information
+            data += varint(o.get("version", 1)) + delta("ts", 0)
+        else:
+            data += varint(o.get("version", 1)) + delta("ts", ts(o["ts"]))
+            data += delta("changeset", o.get("changeset", 1))
+            # Anonymous edits have an empty uid
+            data += string(varint(o["uid"]) if o["uid"] else b"", o["user"].encode())
+        if not o.get("deleted"):
+            if typ == "node":
+                data += delta("lon", round(o.get("lon", 0) * 1e7))
+                data += delta("lat", round(o.get("lat", 0) * 1e7))
+            elif typ == "way":
+                refs = b"".join(delta("node_ref", n) for n in o.get("nodes", []))
+                data += varint(len(refs)) + refs
+            else:
+                refs = b""
+                for mtype, mid, role in o.get("members", []):
+                    t = {"node": "0", "way": "1", "relation": "2"}[mtype]
+                    refs += delta("member" + t, mid) + string((t + role).encode())
+                data += varint(len(refs)) + refs
+            for k, v in o.get("tags", {}).items():
+                data += string(k.encode(), v.encode())
+        out.write(bytes([{"node": 0x10, "way": 0x11, "relation": 0x12}[typ]]) + varint(len(data)) + data)
+    out.write(b"\xfe")
+
+main()
@@ -0,0 +1,117 @@
+#!/usr/bin/env python3
+"""Tiny OSM history PBF writer. Usage: mkpbf.py in.json out.osh.pbf
+
+in.json is a list of objects, or {"objects": [...], "replication_timestamp": 123, "zlib": true}"""
+import json, struct, sys, datetime, zlib
+
+def varint(n):
+    out = b""
+    n &= (1 << 64) - 1
+    while True:
+        b = n & 0x7F
+        n >>= 7
+        if n:
+            out += bytes([b | 0x80])
+        else:
+            return out + bytes([b])
+
+def zz(n):
+    return (n << 1) ^ (n >> 63)
+
+def key(f, wt):
+    return varint((f << 3) | wt)
+
+def f_varint(f, n):
+    return key(f, 0) + varint(n)
+
+def f_bytes(f, b):
+    if isinstance(b, str):
+        b = b.encode()
+    return key(f, 2) + varint(len(b)) + b
+
+def packed(f, vals, enc=lambda x: x):
+    return f_bytes(f, b"".join(varint(enc(v)) for v in vals))
+
+def delta(vals):
+    out, last = [], 0
+    for v in vals:
+        out.append(v - last)
+        last = v
+    return out
+
+def ts(s):
+    # Integers are taken as they are, to make out of range timestamps
+    if isinstance(s, int):
+        return s
+    return int(datetime.datetime.fromisoformat(s.replace("Z", "+00:00")).timestamp())
+
+def main():
+    objs = json.load(open(sys.argv[1]))
+    opts = {}
+    if isinstance(objs, dict):
+        opts, objs = objs, objs["objects"]
+    strings = [""]
+    def sid(s):
+        if s not in strings:
+            strings.append(s)
+        return strings.index(s)
+    groups = []
+    nodes = [o for o in objs if o["type"] == "node"]
+    if nodes:
+        ids = [o["id"] for o in nodes]
+        lats = [round(o.get("lat", 0) * 1e7) for o in nodes]
+        lons = [round(o.get("lon", 0) * 1e7) for o in nodes]
+        kv = []
+        for o in nodes:
+            for k, v in o.get("tags", {}).items():
+                kv += [sid(k), sid(v)]
+            kv.append(0)
+        info = (packed(1, [o.get("version", 1) for o in nodes])
+            + packed(2, delta([ts(o["ts"]) for o in nodes]), zz)
+            + packed(3, delta([o.get("changeset", 1) for o in nodes]), zz)
+            + packed(4, delta([o["uid"] for o in nodes]), zz)
+            + packed(5, delta([sid(o["user"]) for o in nodes]), zz)
+            + packed(6, [0 if o.get("deleted") else 1 for o in nodes]))
+        dense = (packed(1, delta(ids), zz) + f_bytes(5, info)
+                 + packed(8, delta(lats), zz) + packed(9, delta(lons), zz))
+        if any(o.get("tags") for o in nodes):
+            dense += packed(10, kv)
+        groups.append(f_bytes(2, dense))
+    def info(o):
+        return (f_varint(1, o.get("version", 1)) + f_varint(2, ts(o["ts"]))
+                + f_varint(3, o.get("changeset", 1)) + f_varint(4, o["uid"])
+                + f_varint(5, sid(o["user"])) + f_varint(6, 0 if o.get("deleted") else 1))
+    for typ, field in (("way", 3), ("relation", 4)):
+        items = b""
+        for o in [o for o in objs if o["type"] == typ]:
+            tags = o.get("tags", {})
+            body = f_varint(1, o["id"])
+            body += packed(2, [sid(k) for k in tags]) + packed(3, [sid(v) for v in tags.values()])
+            body += f_bytes(4, info(o))
+            if typ == "way":
+                body += packed(8, delta(o.get("nodes", [])), zz)
+            else:
+                mems = o.get("members", [])
+                body += packed(8, [sid(m[2]) for m in mems])
+                body += packed(9, delta([m[1] for m in mems]), zz)
+                body += packed(10, [{"node": 0, "way": 1, "relation": 2}[m[0]] for m in mems])
+            items += f_bytes(field, body)
+        if items:
+            groups.append(items)
+    block = f_bytes(1, b"".join(f_bytes(1, s) for s in strings))
+    for g in groups:
+        block += f_bytes(2, g)
+    block += f_varint(17, 100) + f_varint(18, 1000)
+    header = f_bytes(4, "OsmSchema-V0.6") + f_bytes(4, "DenseNodes") + f_bytes(4, "HistoricalInformation")
+    if "replication_timestamp" in opts:
+        header += f_varint(32, opts["replication_timestamp"])
+    out = open(sys.argv[2], "wb")
+    for typ, data in (("OSMHeader", header), ("OSMData", block)):
+        if opts.get("zlib"):
+            blob = f_varint(2, len(data)) + f_bytes(3, zlib.compress(data))
+        else:
+            blob = f_bytes(1, data) + f_varint(2, len(data))
+        bh = f_bytes(1, typ) + f_varint(3, len(blob))
+        out.write(struct.pack(">I", len(bh)) + bh + blob)
+
+main()
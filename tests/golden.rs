@@ -0,0 +1,2478 @@
+//! Run the binary on small committed history files, and compare the output with known good
+//! ("golden") CSV files in `tests/golden/<test name>/`.
+//!
+//! The fixtures `tests/fixtures/*.osh.pbf` are generated from the `.json` file of the same name
+//! with `python3 tests/fixtures/mkpbf.py tests/fixtures/small.json tests/fixtures/small.osh.pbf`.
+//! The `tests/fixtures/*.o5m` files are generated the same way with `mko5m.py`.
+//!
+//! If a change to the output is intended, rerun with `UPDATE_GOLDEN=1` to rewrite the golden
+//! files, and check the diff.
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::path::Path;
+
+const FIXTURE: &str = "tests/fixtures/small.osh.pbf";
+
+fn run(extra_args: &[&str]) -> (tempfile::TempDir, assert_cmd::assert::Assert) {
+    run_on(FIXTURE, extra_args)
+}
+
+fn run_on(fixture: &str, extra_args: &[&str]) -> (tempfile::TempDir, assert_cmd::assert::Assert) {
+    let output_dir = tempfile::tempdir().unwrap();
+    let prefix = format!("{}/", output_dir.path().display());
+    let assert = Command::cargo_bin("osm-num-active-contributors")
+        .unwrap()
+        .args(["-i", fixture, "-p", &prefix])
+        .args(extra_args)
+        .assert();
+    (output_dir, assert)
+}
+
+/// Run with these arguments, and compare each of `files` with the golden copy.
+fn check_golden(name: &str, extra_args: &[&str], files: &[&str]) {
+    let (output_dir, assert) = run(extra_args);
+    assert.success();
+
+    let golden_dir = Path::new("tests/golden").join(name);
+    for file in files {
+        let output = std::fs::read_to_string(output_dir.path().join(file))
+            .unwrap_or_else(|e| panic!("output file {} not written: {}", file, e));
+        let golden_path = golden_dir.join(file);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(&golden_dir).unwrap();
+            std::fs::write(&golden_path, &output).unwrap();
+            continue;
+        }
+        let golden = std::fs::read_to_string(&golden_path)
+            .unwrap_or_else(|e| panic!("golden file {} missing: {}", golden_path.display(), e));
+        assert!(
+            output == golden,
+            "{} differs from {}",
+            file,
+            golden_path.display()
+        );
+    }
+}
+
+#[test]
+fn default_run() {
+    check_golden(
+        "default",
+        &[],
+        &["user_totals_per_day.csv", "users_per_day.csv"],
+    );
+}
+
+#[test]
+fn min_edit_days() {
+    check_golden(
+        "min_edit_days_0",
+        &["--min-edit-days", "0"],
+        &["users_per_day.csv"],
+    );
+}
+
+#[test]
+fn date_range() {
+    check_golden(
+        "date_range",
+        &[
+            "--start-date",
+            "2023-02-01",
+            "--end-date",
+            "2023-02-10",
+            "--min-edit-days",
+            "10",
+        ],
+        &["users_per_day.csv"],
+    );
+}
+
+#[test]
+fn end_exclusive() {
+    let golden = std::fs::read_to_string("tests/golden/date_range/users_per_day.csv").unwrap();
+    let args = [
+        "--start-date",
+        "2023-02-01",
+        "--end-date",
+        "2023-02-11",
+        "--min-edit-days",
+        "10",
+        "--end-exclusive",
+    ];
+    let (output_dir, assert) = run(&args);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(output, golden);
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(totals.contains("\n2023-02-10,"), "{}", totals);
+    assert!(!totals.contains("\n2023-02-11,"), "{}", totals);
+
+    let (output_dir, assert) = run(&[&args[..], &["--end-inclusive"]].concat());
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.starts_with(&golden), "{}", output);
+    assert!(output.contains("\n2023-02-11,"), "{}", output);
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert_eq!(
+        totals.lines().last().unwrap().split(',').next(),
+        Some("2023-02-11")
+    );
+}
+
+#[test]
+fn filter_matching_nothing() {
+    let (_output_dir, assert) = run(&["--min-object-version", "1000"]);
+    assert
+        .code(5)
+        .stderr(predicates::str::contains("No objects found"));
+}
+
+#[test]
+fn require_tags() {
+    let (_output_dir, assert) = run(&["--require-tags"]);
+    assert.success();
+    // None of these objects have tags
+    let (_output_dir, assert) = run_on("tests/fixtures/timezones.osh.pbf", &["--require-tags"]);
+    assert
+        .code(5)
+        .stderr(predicates::str::contains("No objects found"));
+}
+
+#[test]
+fn exclude_weekends() {
+    let (output_dir, assert) = run(&["--exclude-weekends", "--min-edit-days", "0"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    // 2023-01-01 is a Sunday
+    assert!(output.contains("\n2023-01-02,1,1,"), "{}", output);
+    assert!(!output.contains("\n2023-01-01,"), "{}", output);
+    assert!(output.contains("\n2023-01-07,0,2,"), "{}", output);
+    assert!(output.contains("\n2023-01-08,0,2,"), "{}", output);
+}
+
+#[test]
+fn exclude_range() {
+    let (output_dir, assert) = run(&[
+        "--exclude-range",
+        "2023-01-05..2023-01-06",
+        "--exclude-range",
+        "2023-01-10..2023-01-10",
+        "--min-edit-days",
+        "0",
+    ]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let num_users = |date: &str| {
+        let line = output.lines().find(|line| line.starts_with(date)).unwrap();
+        line.split(',').nth(1).unwrap().to_owned()
+    };
+    assert_eq!(num_users("2023-01-04"), "1", "{}", output);
+    assert_eq!(num_users("2023-01-05"), "0", "{}", output);
+    assert_eq!(num_users("2023-01-06"), "0", "{}", output);
+    assert_eq!(num_users("2023-01-07"), "1", "{}", output);
+    assert_eq!(num_users("2023-01-10"), "0", "{}", output);
+
+    let (_output_dir, assert) = run(&["--exclude-range", "2023-01-06..2023-01-05"]);
+    assert
+        .code(2)
+        .stderr(predicates::str::contains("2023-01-06 is after 2023-01-05"));
+}
+
+#[test]
+fn output_prefix_directory() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let prefix = |path: &str| format!("{}/{}", output_dir.path().display(), path);
+    for path in ["new/sub/", "new/sub/x_", "new"] {
+        Command::cargo_bin("osm-num-active-contributors")
+            .unwrap()
+            .args(["-i", FIXTURE, "-p", &prefix(path)])
+            .assert()
+            .success();
+    }
+    for file in [
+        "new/sub/users_per_day.csv",
+        "new/sub/x_users_per_day.csv",
+        "new/users_per_day.csv",
+    ] {
+        assert!(output_dir.path().join(file).exists(), "{}", file);
+    }
+}
+
+#[test]
+fn min_num_days_longer_than_file() {
+    for min_num_days in ["100", "4294967295"] {
+        let (output_dir, assert) = run_on(
+            "tests/fixtures/timezones.osh.pbf",
+            &[
+                "--min-edit-days",
+                "0",
+                "--start-date",
+                "2023-01-02",
+                "--min-num-days",
+                min_num_days,
+            ],
+        );
+        assert.success();
+        let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+        let dates: std::collections::BTreeSet<_> = output
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(
+            dates,
+            std::collections::BTreeSet::from(["2023-01-01", "2023-01-02"]),
+            "{}",
+            output
+        );
+    }
+}
+
+#[test]
+fn no_objects() {
+    let (_output_dir, assert) = run_on("tests/fixtures/empty.osh.pbf", &[]);
+    assert
+        .code(3)
+        .stderr(predicates::str::contains("No objects found"));
+}
+
+#[test]
+fn not_a_pbf_file() {
+    let (_output_dir, assert) = run_on("tests/fixtures/small.json", &[]);
+    assert
+        .code(4)
+        .stderr(predicates::str::contains("is not an OSM PBF or o5m file"));
+}
+
+/// Write a broken copy of this fixture to a temporary file, and run on it.
+fn run_on_broken(fixture: &str, break_data: impl Fn(&mut Vec<u8>)) -> assert_cmd::assert::Assert {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(Path::new(fixture).file_name().unwrap());
+    let mut data = std::fs::read(fixture).unwrap();
+    break_data(&mut data);
+    std::fs::write(&path, data).unwrap();
+    run_on(path.to_str().unwrap(), &[]).1
+}
+
+/// A cut off or corrupt file is a clean error, not a panic.
+#[test]
+fn malformed_input() {
+    run_on_broken(FIXTURE, |data| data.truncate(300))
+        .code(7)
+        .stderr(predicates::str::contains(
+            "is malformed: blob 2 at byte 72 is cut off",
+        ));
+    run_on_broken(FIXTURE, |data| {
+        for byte in &mut data[200..260] {
+            *byte ^= 0x5a;
+        }
+    })
+    .code(7)
+    .stderr(
+        predicates::str::contains("is malformed: ")
+            .and(predicates::str::contains("panicked").not()),
+    );
+    run_on_broken("tests/fixtures/small.o5m", |data| data.truncate(1000))
+        .code(7)
+        .stderr(predicates::str::contains(
+            "is malformed: o5m file ends in the middle of a dataset",
+        ))
+        .stderr(predicates::str::contains("after reading 75 objects"));
+}
+
+/// A file split into parts is read as one, wherever the parts are split.
+#[test]
+fn input_parts() {
+    let data = std::fs::read(FIXTURE).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    for (num, part) in data.chunks(100).enumerate() {
+        std::fs::write(dir.path().join(format!("small.osh.pbf.part{}", num)), part).unwrap();
+    }
+    let parts = format!("{}/small.osh.pbf.part*", dir.path().display());
+    let (output_dir, assert) = run_on(&parts, &[]);
+    assert.success();
+    assert_eq!(
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap(),
+        std::fs::read_to_string("tests/golden/default/user_totals_per_day.csv").unwrap()
+    );
+
+    std::fs::remove_file(dir.path().join("small.osh.pbf.part2")).unwrap();
+    run_on(&parts, &[])
+        .1
+        .failure()
+        .stderr(predicates::str::contains("Part 2 of "))
+        .stderr(predicates::str::contains("small.osh.pbf.part* is missing"));
+
+    // Without the last part, the last blob is cut off
+    std::fs::write(dir.path().join("small.osh.pbf.part2"), &data[200..300]).unwrap();
+    let last_part = data.len().div_ceil(100) - 1;
+    std::fs::remove_file(dir.path().join(format!("small.osh.pbf.part{}", last_part))).unwrap();
+    run_on(&parts, &[])
+        .1
+        .code(7)
+        .stderr(predicates::str::contains("is malformed: blob "));
+}
+
+#[test]
+fn missing_input_file() {
+    let (_output_dir, assert) = run_on("tests/fixtures/does-not-exist.osh.pbf", &[]);
+    assert.code(6);
+}
+
+/// uid 5 only has objects with a blank username, so has no username at all.
+#[test]
+fn blank_username() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/blank_username.osh.pbf",
+        &["--min-edit-days", "0"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.contains("2023-01-03,5,2,(unknown),"), "{}", output);
+    assert!(output.contains("2023-01-03,1,1,alice,"), "{}", output);
+}
+
+/// Both files must agree on what "active" means.
+#[test]
+fn active_fraction() {
+    let (output_dir, assert) = run(&["--active-fraction", "0.101", "--window-days", "100"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.starts_with("date,num_users,rolling_yr_total,users_ge11_days,"));
+    let (_output_dir, assert) = run(&["--active-fraction", "0.1", "--active-threshold", "10"]);
+    assert.failure();
+}
+
+#[test]
+fn active_threshold_headers() {
+    let (output_dir, assert) = run(&["--active-threshold", "30", "--min-edit-days", "0"]);
+    assert.success();
+    let header = |file: &str| {
+        let output = std::fs::read_to_string(output_dir.path().join(file)).unwrap();
+        output.lines().next().unwrap().to_owned()
+    };
+    assert_eq!(
+        header("user_totals_per_day.csv"),
+        "date,num_users,rolling_yr_total,users_ge30_days,pct_of_alltime_contributors,median_account_age_days,at_risk_users"
+    );
+    assert_eq!(
+        header("users_per_day.csv"),
+        "date,uid,num_edit_days_last_yr,username,ge30days,mapped_days,likely_mechanical,account_age_days,density,pattern"
+    );
+}
+
+/// Files from `--input-list` are read as well as `-i` ones.
+#[test]
+fn input_list() {
+    let list_dir = tempfile::tempdir().unwrap();
+    let list = list_dir.path().join("inputs.txt");
+    std::fs::write(
+        &list,
+        "# extracts\n\ntests/fixtures/blank_username.osh.pbf\n",
+    )
+    .unwrap();
+    let (output_dir, assert) = run(&[
+        "--input-list",
+        list.to_str().unwrap(),
+        "--min-edit-days",
+        "0",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    // uid 5 is only in blank_username.osh.pbf, bob only in small.osh.pbf
+    assert!(output.contains(",5,2,(unknown),"), "{}", output);
+    assert!(output.contains(",2,1,bob,"), "{}", output);
+}
+
+#[test]
+fn tier_transitions() {
+    let (output_dir, assert) = run(&["--tier-transitions"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("tier_transitions.csv")).unwrap();
+    // alice becomes core in February, and carol starts in March
+    assert_eq!(
+        output,
+        "date,casual,regular,core,moved_up,moved_down\n\
+         2023-01-31,0,2,0,2,0\n\
+         2023-02-28,0,1,1,1,0\n\
+         2023-03-31,1,1,1,1,0\n\
+         2023-04-10,1,1,1,0,0\n"
+    );
+    let (output_dir, assert) = run(&["--tier-transitions", "--window-days", "30"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("tier_transitions.csv")).unwrap();
+    assert!(output.contains("\n2023-03-31,1,0,0,1,2\n"), "{}", output);
+}
+
+#[test]
+fn inter_arrival() {
+    let (output_dir, assert) = run(&["--inter-arrival"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("inter_arrival.csv")).unwrap();
+    assert!(
+        output.contains("\n2,bob,26,2.12,2.0,5,\"5,2,"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n3,carol,2,29.0,29.0,29,29\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn stacked_cohorts() {
+    let (output_dir, assert) = run(&["--stacked-cohorts"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("stacked_cohorts.csv")).unwrap();
+    assert!(
+        output.starts_with("date,2023\n2023-01-01,1\n"),
+        "{}",
+        output
+    );
+    assert!(output.ends_with("\n2023-04-10,3\n"), "{}", output);
+}
+
+#[test]
+fn profiles() {
+    let profiles_dir = tempfile::tempdir().unwrap();
+    let path = profiles_dir.path().join("profiles.jsonl");
+    let (_output_dir, assert) = run(&["--profiles", path.to_str().unwrap()]);
+    assert.success();
+    let output = std::fs::read_to_string(&path).unwrap();
+    let profiles: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(profiles.len(), 3);
+    assert_eq!(
+        profiles[2],
+        serde_json::json!({
+            "uid": 3, "username": "carol", "first_edit": "2023-03-12", "last_edit": "2023-04-10",
+            "last_edit_ts": "2023-04-10T15:00:00Z",
+            "num_edit_days": 2, "longest_streak_days": 1, "longest_gap_days": 29, "years_active": 1,
+            "consistency": 1.0, "num_edits": 3, "num_changesets": 2,
+            "bbox": [18.4, -33.9, 18.4, -33.9],
+        })
+    );
+    assert_eq!(profiles[0]["longest_streak_days"], 50);
+}
+
+#[test]
+fn grafana() {
+    let grafana_dir = tempfile::tempdir().unwrap();
+    let path = grafana_dir.path().join("grafana.json");
+    let (_output_dir, assert) = run(&["--grafana", path.to_str().unwrap(), "--window-days", "5"]);
+    assert.success();
+    let targets: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    let targets = targets.as_array().unwrap();
+    let names: Vec<&str> = targets
+        .iter()
+        .map(|target| target["target"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "num_users",
+            "new_users",
+            "churned_users",
+            "rolling_yr_total"
+        ]
+    );
+    for target in targets {
+        assert_eq!(target["datapoints"].as_array().unwrap().len(), 100);
+        // 2023-01-01
+        assert_eq!(target["datapoints"][0][1], 1672531200000_i64);
+    }
+    let total = |target: &serde_json::Value| {
+        target["datapoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|datapoint| datapoint[0].as_u64().unwrap())
+            .sum::<u64>()
+    };
+    assert_eq!(total(&targets[1]), 3);
+    // alice & bob stop, and carol leaves the window between her 2 edits
+    assert_eq!(total(&targets[2]), 3);
+}
+
+/// alice edited in 2023 & 2024, bob in 2024 & 2031.
+#[test]
+fn profile_consistency() {
+    let profiles_dir = tempfile::tempdir().unwrap();
+    let path = profiles_dir.path().join("profiles.jsonl");
+    let (_output_dir, assert) = run_on(
+        "tests/fixtures/future.osh.pbf",
+        &["--profiles", path.to_str().unwrap()],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(&path).unwrap();
+    let profiles: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(profiles[0]["years_active"], 2);
+    assert_eq!(profiles[0]["consistency"], 1.0);
+    assert_eq!(profiles[1]["years_active"], 2);
+    assert_eq!(profiles[1]["consistency"], 0.25);
+}
+
+/// carol made an untagged node, a way with 1 tag & a relation with 1 tag.
+#[test]
+fn weight_by() {
+    let profiles_dir = tempfile::tempdir().unwrap();
+    let path = profiles_dir.path().join("profiles.jsonl");
+    for (weight_by, weighted_edits) in [("tags", 5), ("type", 6)] {
+        let (_output_dir, assert) = run(&[
+            "--profiles",
+            path.to_str().unwrap(),
+            "--weight-by",
+            weight_by,
+        ]);
+        assert.success();
+        let output = std::fs::read_to_string(&path).unwrap();
+        let carol: serde_json::Value =
+            serde_json::from_str(output.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(carol["num_edits"], 3);
+        assert_eq!(carol["weighted_edits"], weighted_edits, "{}", weight_by);
+    }
+}
+
+#[test]
+fn reactivation() {
+    let (output_dir, assert) = run(&["--reactivation", "--reactivation-gap-days", "20"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("reactivation.csv")).unwrap();
+    assert_eq!(
+        output,
+        "uid,username,last_edit_before,return_date,gap_days\n3,carol,2023-03-12,2023-04-10,29\n"
+    );
+}
+
+#[test]
+fn top_n_per_day() {
+    let (output_dir, assert) = run(&["--min-edit-days", "0", "--top-n-per-day", "2"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let last_day: Vec<_> = output
+        .lines()
+        .filter(|line| line.starts_with("2023-04-10,"))
+        .map(|line| line.split(',').nth(3).unwrap())
+        .collect();
+    assert_eq!(last_day, ["alice2", "bob"], "{}", output);
+}
+
+/// On 2023-01-02, all 4 users have edited on 1 day.
+#[test]
+fn top_n_per_day_ties() {
+    let mut outputs = Vec::new();
+    for _ in 0..2 {
+        let (output_dir, assert) = run_on(
+            "tests/fixtures/timezones.osh.pbf",
+            &["--min-edit-days", "0", "--top-n-per-day", "2"],
+        );
+        assert.success();
+        outputs.push(std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap());
+    }
+    let uids: Vec<_> = outputs[0]
+        .lines()
+        .filter(|line| line.starts_with("2023-01-02,"))
+        .map(|line| line.split(',').nth(1).unwrap())
+        .collect();
+    assert_eq!(uids, ["1", "2"], "{}", outputs[0]);
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn max_edit_days() {
+    let (output_dir, assert) = run(&["--min-edit-days", "2", "--max-edit-days", "20"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    for line in output.lines().skip(1) {
+        let num_days: u32 = line.split(',').nth(2).unwrap().parse().unwrap();
+        assert!((2..=20).contains(&num_days), "{}", line);
+    }
+    assert!(output.lines().count() > 1, "{}", output);
+}
+
+#[test]
+fn format_tidy() {
+    let (output_dir, assert) = run(&["--format", "tidy"]);
+    assert.success();
+    assert!(!output_dir.path().join("user_totals_per_day.csv").exists());
+    let output = std::fs::read_to_string(output_dir.path().join("metrics_long.csv")).unwrap();
+    assert!(output.starts_with("date,metric,value\n2023-01-01,num_users,1\n"));
+    assert!(
+        output.contains("\n2023-02-19,users_ge42_days,1\n"),
+        "{}",
+        output
+    );
+}
+
+/// Reading the same file twice mustn't double anyone's edit count. With these thresholds, a user
+/// is `likely_mechanical` if they make more than 1 edit per edit day, and alice & bob make exactly
+/// 1.
+#[test]
+fn overlapping_inputs() {
+    let (output_dir, assert) = run(&[
+        "-i",
+        FIXTURE,
+        "--min-edit-days",
+        "0",
+        "--mechanical-edits-per-changeset",
+        "0",
+        "--mechanical-edits-per-day",
+        "1",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let mut reader = csv::Reader::from_reader(output.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    let column = |name| headers.iter().position(|h| h == name).unwrap();
+    let (username, likely_mechanical) = (column("username"), column("likely_mechanical"));
+    for row in reader.records() {
+        let row = row.unwrap();
+        if &row[username] != "carol" {
+            assert_eq!(&row[likely_mechanical], "no", "{:?}", row);
+        }
+    }
+}
+
+#[test]
+fn single_user() {
+    let (output_dir, assert) = run(&["--user", "3"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("user_3.csv")).unwrap();
+    assert_eq!(
+        output,
+        "date,num_edits,num_changesets,node_edits,way_edits,relation_edits\n\
+         2023-03-12,2,1,1,1,0\n\
+         2023-04-10,1,1,0,0,1\n"
+    );
+}
+
+/// An unknown `--format` is an argument error, which lists the formats.
+#[test]
+fn unknown_format() {
+    let (_output_dir, assert) = run(&["--format", "parquet"]);
+    assert.code(2).stderr(predicates::str::contains(
+        "[possible values: csv, tidy, arrow]",
+    ));
+}
+
+/// The Arrow file has the same rows & columns as the CSV file.
+#[test]
+fn format_arrow() {
+    let (output_dir, assert) = run(&["--format", "arrow", "--active-threshold", "30"]);
+    assert.success();
+    assert!(!output_dir.path().join("users_per_day.csv").exists());
+    let file = std::fs::File::open(output_dir.path().join("users_per_day.arrow")).unwrap();
+    let reader = arrow_ipc::reader::FileReader::try_new(file, None).unwrap();
+    let columns: Vec<_> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(
+        columns.join(","),
+        "date,uid,num_edit_days_last_yr,username,ge30days,mapped_days,likely_mechanical,account_age_days,density,pattern"
+    );
+    let num_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+    let golden = std::fs::read_to_string("tests/golden/default/users_per_day.csv").unwrap();
+    assert_eq!(num_rows, golden.lines().count() - 1);
+}
+
+#[test]
+fn seasonality() {
+    let (output_dir, assert) = run(&["--seasonality"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("seasonality.csv")).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 13);
+    // 78 user days over 100 days in total
+    assert_eq!(lines[1], "1,31,1.39,1.78");
+    assert_eq!(lines[4], "4,10,0.1,0.13");
+    assert_eq!(lines[12], "12,0,,");
+}
+
+#[test]
+fn heatmap() {
+    let (output_dir, assert) = run(&["--heatmap"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("heatmap.csv")).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    // 2023-01-01 is a Sunday, so in week 52 of 2022
+    assert_eq!(lines[0], "week,2022,2023");
+    assert_eq!(lines[1], "1,0,2");
+    assert_eq!(lines[2], "2,0,2");
+    assert_eq!(lines[52], "52,1,0");
+    assert_eq!(lines.len(), 54);
+}
+
+#[test]
+fn excel_safe() {
+    let (output_dir, assert) = run(&["--excel-safe", "--min-edit-days", "0", "--emit-events"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2023-01-01,\"=\"\"1\"\"\",1,alice2,no,"),
+        "{}",
+        output
+    );
+    let output = std::fs::read_to_string(output_dir.path().join("edit_events.csv")).unwrap();
+    assert!(
+        output.starts_with("uid,date\n\"=\"\"1\"\"\",2023-01-01\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn date_format() {
+    let (output_dir, assert) = run(&[
+        "--date-format",
+        "%d.%m.%Y",
+        "--min-edit-days",
+        "0",
+        "--stacked-cohorts",
+        "--experience-bands",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n02.01.2023,1,2,alice2,no,\"01.01.2023,02.01.2023\",no,1,1.0,burst\n"),
+        "{}",
+        output
+    );
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(totals.contains("\n02.01.2023,1,"), "{}", totals);
+    // Both files have the same dates, so they can be joined
+    let dates = |output: &str| -> std::collections::BTreeSet<String> {
+        output
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(dates(&output), dates(&totals));
+    for file in ["stacked_cohorts.csv", "experience_bands.csv"] {
+        let other = std::fs::read_to_string(output_dir.path().join(file)).unwrap();
+        assert_eq!(dates(&other), dates(&totals), "{}", file);
+    }
+
+    let (_output_dir, assert) = run(&["--date-format", "%Q"]);
+    assert
+        .code(2)
+        .stderr(predicates::str::contains("is not a valid strftime format"));
+}
+
+/// On 2023-03-05, the window is 2023-02-26 to 2023-03-12, when carol first edits.
+/// dave edits on 2023-01-01, alice on 2023-02-28, bob on 2023-03-01, and carol on 2024-02-29 &
+/// 2024-03-01.
+#[test]
+fn window_across_leap_day() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/leap_year.osh.pbf",
+        &["--min-edit-days", "0"],
+    );
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let rolling_yr_total = |date: &str| {
+        let line = output.lines().find(|line| line.starts_with(date)).unwrap();
+        line.split(',').nth(2).unwrap().parse::<u32>().unwrap()
+    };
+    // 365 days before a date is still in its window
+    assert_eq!(rolling_yr_total("2023-12-31"), 3, "{}", output);
+    assert_eq!(rolling_yr_total("2024-01-01"), 3, "{}", output);
+    assert_eq!(rolling_yr_total("2024-01-02"), 2, "{}", output);
+    // With the leap day, 365 days before 2024-02-29 is 2023-03-01, not 2023-02-28
+    assert_eq!(rolling_yr_total("2024-02-28"), 2, "{}", output);
+    assert_eq!(rolling_yr_total("2024-02-29"), 2, "{}", output);
+    assert_eq!(rolling_yr_total("2024-03-01"), 1, "{}", output);
+    let users = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let uids: Vec<_> = users
+        .lines()
+        .filter(|line| line.starts_with("2024-02-29,"))
+        .map(|line| line.split(',').nth(1).unwrap())
+        .collect();
+    assert_eq!(uids, ["2", "3"], "{}", users);
+}
+
+#[test]
+fn window_mode_centered() {
+    let (output_dir, assert) = run(&[
+        "--window-days",
+        "14",
+        "--min-edit-days",
+        "0",
+        "--window-mode",
+        "centered",
+    ]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2023-03-05,0,2,0,66.67,25.5,0\n"),
+        "{}",
+        output
+    );
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2023-03-05,3,1,carol,no,2023-03-12,no,-7,1.0,burst\n"),
+        "{}",
+        output
+    );
+}
+
+/// carol's 2 edit days are 29 days apart, which is outside a 5 day calendar window.
+#[test]
+fn window_mode_active_days() {
+    let args = ["--window-days", "5", "--min-edit-days", "0"];
+    let (output_dir, assert) = run(&[&args[..], &["--window-mode", "active-days"]].concat());
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output
+            .contains("\n2023-04-10,3,2,carol,no,\"2023-03-12,2023-04-10\",no,29,0.07,sporadic\n"),
+        "{}",
+        output
+    );
+
+    let (output_dir, assert) = run(&args);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2023-04-10,3,1,carol,no,2023-04-10,no,29,1.0,burst\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn first_n_days() {
+    let (output_dir, assert) = run(&["--first-n-days", "10", "--inter-arrival"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("inter_arrival.csv")).unwrap();
+    // alice only renamed after her first 10 days
+    assert!(output.contains("\n1,alice,10,"), "{}", output);
+    // 06.01., 11.01., 13.01. & 15.01.
+    assert!(output.contains("\n2,bob,4,"), "{}", output);
+    assert!(output.contains("\n3,carol,1,"), "{}", output);
+}
+
+/// alice renamed, and back, but her first & last edits are read before the one with the other
+/// name. Her last edit is still the later one, so her last username is `alice`.
+#[test]
+fn last_username_out_of_order() {
+    let (output_dir, assert) = run_on("tests/fixtures/renames.osh.pbf", &["--min-edit-days", "0"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.contains("\n2023-03-01,1,3,alice,"), "{}", output);
+    assert!(!output.contains("alice_old"), "{}", output);
+}
+
+/// dave never renames, and his edits aren't read in time order. His last one has no username,
+/// but it's still his latest edit.
+#[test]
+fn last_edit_ts() {
+    let profiles_dir = tempfile::tempdir().unwrap();
+    let path = profiles_dir.path().join("profiles.jsonl");
+    let (_output_dir, assert) = run_on(
+        "tests/fixtures/latest_edit.osh.pbf",
+        &["--profiles", path.to_str().unwrap(), "--min-edit-days", "0"],
+    );
+    assert.success();
+    let profile: serde_json::Value =
+        serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim_end()).unwrap();
+    assert_eq!(profile["username"], "dave");
+    assert_eq!(profile["last_edit_ts"], "2023-03-01T18:30:00Z");
+}
+
+/// Anonymous edits are all uid 0, always called `(anonymous)`, even if one has a username.
+#[test]
+fn anonymous_edits() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/anonymous.osh.pbf",
+        &["--min-edit-days", "0"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2007-01-02,0,2,(anonymous),"),
+        "{}",
+        output
+    );
+    assert!(output.contains("\n2007-01-02,1,1,alice,"), "{}", output);
+    assert!(!output.contains("someone"), "{}", output);
+}
+
+/// bob's edits on 06.01. & 11.01. are bits 9 & 14 of the 16 day window, i.e. `0x00 0x42`.
+#[test]
+fn edit_day_bitmap() {
+    let (output_dir, assert) = run(&[
+        "--window-days",
+        "15",
+        "--emit-bitmap",
+        "--min-edit-days",
+        "0",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",density,pattern,edit_day_bitmap"));
+    assert!(
+        output.contains("\n2023-01-12,2,2,bob,no,\"2023-01-06,2023-01-11\",no,6,0.33,burst,AEI=\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn compare() {
+    let old_dir = tempfile::tempdir().unwrap();
+    let old = old_dir.path().join("old.csv");
+    let golden = std::fs::read_to_string("tests/golden/default/user_totals_per_day.csv").unwrap();
+    std::fs::write(&old, golden.replace("\n2023-01-05,1,", "\n2023-01-05,7,")).unwrap();
+    let (_output_dir, assert) = run(&["--compare", old.to_str().unwrap()]);
+    assert.success().stdout(predicates::str::contains(
+        "\n2023-01-05: num_users 7 → 1\n1 dates differ\n",
+    ));
+}
+
+/// carol's changeset on 2023-03-12 is the only one with more than 1 object.
+#[test]
+fn exclude_large_changesets() {
+    for flag in [
+        &["--exclude-changesets-over", "1"][..],
+        &["--exclude-largest-changeset"],
+    ] {
+        let (output_dir, assert) = run(&[flag, &["--user", "3"]].concat());
+        assert.success();
+        let output = std::fs::read_to_string(output_dir.path().join("user_3.csv")).unwrap();
+        assert!(!output.contains("2023-03-12"), "{:?}: {}", flag, output);
+        assert!(
+            output.contains("\n2023-04-10,1,1,0,0,1\n"),
+            "{:?}: {}",
+            flag,
+            output
+        );
+    }
+}
+
+/// alice2 edits on 50 days, & bob on 26, 21 of them new.
+#[test]
+fn merge_uids() {
+    let merge_dir = tempfile::tempdir().unwrap();
+    let merge_uids = merge_dir.path().join("merge_uids.txt");
+    std::fs::write(&merge_uids, "# bob is alice2\n2=1\n").unwrap();
+    let (output_dir, assert) = run(&[
+        "--merge-uids",
+        merge_uids.to_str().unwrap(),
+        "--min-edit-days",
+        "0",
+    ]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 2 users & 57 days"));
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.contains("\n2023-02-28,1,55,bob,yes,"), "{}", output);
+    assert!(
+        output
+            .lines()
+            .all(|line| line.split(',').nth(1) != Some("2")),
+        "{}",
+        output
+    );
+
+    std::fs::write(&merge_uids, "2=1\n1=3\n").unwrap();
+    let (_output_dir, assert) = run(&["--merge-uids", merge_uids.to_str().unwrap()]);
+    assert.failure().stderr(predicates::str::contains(
+        "uid 2 is merged into 1, which is merged into another uid itself",
+    ));
+}
+
+/// carol makes 2 edits on 2023-03-12, everyone else 1 edit on each day.
+/// More days than there can be dates keeps every edit.
+#[test]
+fn first_n_days_huge() {
+    let (output_dir, assert) = run(&["--first-n-days", "18446744073709551615"]);
+    assert.success();
+    assert_eq!(
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap(),
+        std::fs::read_to_string("tests/golden/default/user_totals_per_day.csv").unwrap()
+    );
+}
+
+#[test]
+fn min_edits_per_day() {
+    let (output_dir, assert) = run(&["--min-edits-per-day", "2", "--min-edit-days", "0"]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 1 users & 1 days"));
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(
+        output.lines().nth(1),
+        Some("2023-03-12,3,1,carol,no,2023-03-12,no,0,1.0,burst")
+    );
+}
+
+#[test]
+fn active_day_expr() {
+    // The same as `--min-edits-per-day 2`
+    let (_output_dir, assert) = run(&[
+        "--active-day-expr",
+        "edit_count >= 2",
+        "--min-edit-days",
+        "0",
+    ]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 1 users & 1 days"));
+
+    // bob's untagged node edits, not his way
+    let (output_dir, assert) = run(&[
+        "--active-day-expr",
+        "!has_tags && (distinct_objects < 2 || edit_count > 10)",
+        "--min-edit-days",
+        "0",
+    ]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 1 users & 25 days"));
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(
+        output.lines().nth(1),
+        Some("2023-01-11,2,1,bob,no,2023-01-11,no,0,1.0,burst")
+    );
+
+    for (expr, error) in [
+        ("edit_count >=", "but the expression ends"),
+        ("edits > 1", "there's no feature \"edits\""),
+        (
+            "edit_count > 1 )",
+            "expected `&&`, `||` or the end at \")\"",
+        ),
+    ] {
+        run(&["--active-day-expr", expr])
+            .1
+            .failure()
+            .stderr(predicates::str::contains(error));
+    }
+}
+
+#[test]
+fn min_changeset_size() {
+    let (output_dir, assert) = run(&["--min-changeset-size", "2", "--user", "3"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("user_3.csv")).unwrap();
+    assert!(output.ends_with("\n2023-03-12,2,1,1,1,0\n"), "{}", output);
+    // Everyone else only has 1 object per changeset
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert_eq!(totals.lines().nth(1), Some("2023-03-12,1,1,0,100.0,0.0,0"));
+}
+
+/// bob's 2nd edit is years after the replication timestamp, and his 1st an hour after it.
+#[test]
+fn ignore_future_edits() {
+    let (output_dir, assert) = run_on("tests/fixtures/future.osh.pbf", &["--min-edit-days", "0"]);
+    assert.success();
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(totals.contains("\n2031-05-01,1,"), "{}", totals);
+
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/future.osh.pbf",
+        &["--min-edit-days", "0", "--ignore-future-edits"],
+    );
+    assert.success().stdout(predicates::str::contains(
+        "Skipped 2 objects with a timestamp after the replication timestamp\n",
+    ));
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let last = totals.lines().last().unwrap();
+    assert!(last.starts_with("2024-01-01,1,"), "{}", totals);
+
+    let (_output_dir, assert) = run(&["--ignore-future-edits", "--range-only"]);
+    assert.success().stderr(predicates::str::contains(
+        "no input has a replication timestamp",
+    ));
+}
+
+/// The first 10 objects are alice's first 10 days.
+#[test]
+fn max_objects() {
+    let (output_dir, assert) = run(&["--max-objects", "10", "--first-n-days", "100"]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 1 users & 10 days"))
+        .stderr(predicates::str::contains(
+            "Stopped reading after 10 objects (--max-objects), so the output is partial",
+        ));
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.ends_with("\n2023-01-10,1,1,0,100.0,9.0,0\n"),
+        "{}",
+        output
+    );
+
+    let (_output_dir, assert) = run(&["--max-objects", "1000"]);
+    assert
+        .success()
+        .stderr(predicates::str::contains("--max-objects").not());
+}
+
+#[test]
+fn replication_timestamp() {
+    let (_output_dir, assert) = run_on("tests/fixtures/replication.osh.pbf", &["--range-only"]);
+    assert.success().stdout(predicates::str::contains(
+        "replication.osh.pbf: data current as of 2024-01-01 12:00:00 UTC\n",
+    ));
+
+    let (_output_dir, assert) = run(&["--range-only"]);
+    assert
+        .success()
+        .stdout(predicates::str::contains("current as of").not());
+}
+
+/// carol never has 20 edit days, and bob only has 12 by 2023-02-01, but 25 by 2023-02-27.
+#[test]
+fn min_total_edit_days() {
+    let (output_dir, assert) = run(&[
+        "--window-days",
+        "5",
+        "--min-edit-days",
+        "0",
+        "--min-total-edit-days",
+        "20",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(!output.contains(",carol,"), "{}", output);
+    assert!(output.contains("\n2023-02-27,2,3,bob,"), "{}", output);
+    assert!(!output.contains("\n2023-02-01,2,"), "{}", output);
+}
+
+/// bob & carol both edited way 10.
+#[test]
+fn co_edit_graph() {
+    let (output_dir, assert) = run(&["--co-edit-graph"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("co_edit_graph.csv")).unwrap();
+    assert_eq!(output, "uid_a,uid_b,shared_objects\n2,3,1\n");
+
+    let (output_dir, assert) = run(&["--co-edit-graph", "--max-object-editors", "1"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("co_edit_graph.csv")).unwrap();
+    assert_eq!(output, "");
+}
+
+/// The same history in o5m format gives the same output.
+#[test]
+fn o5m_input() {
+    let (output_dir, assert) = run_on("tests/fixtures/small.o5m", &[]);
+    assert.success();
+    for file in ["user_totals_per_day.csv", "users_per_day.csv"] {
+        let output = std::fs::read_to_string(output_dir.path().join(file)).unwrap();
+        let golden = std::fs::read_to_string(Path::new("tests/golden/default").join(file)).unwrap();
+        assert!(output == golden, "{} differs from the PBF input", file);
+    }
+}
+
+#[test]
+fn read_from() {
+    let (output_dir, assert) = run(&["--read-from", "2023-02-01"]);
+    assert.success().stderr(predicates::str::contains(
+        "Warning: --read-from skips all edits before 2023-02-01",
+    ));
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let first_date = output.lines().nth(1).unwrap().split(',').next().unwrap();
+    assert!(first_date >= "2023-02-01", "{}", output);
+}
+
+/// Arguments from `--config`, with the command line overriding them.
+#[test]
+fn config_file() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let config = output_dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        format!(
+            r#"{{"input_filename": ["{}"], "output_prefix": "{}/", "min_edit_days": 10}}"#,
+            FIXTURE,
+            output_dir.path().display()
+        ),
+    )
+    .unwrap();
+    Command::cargo_bin("osm-num-active-contributors")
+        .unwrap()
+        .args(["--config", config.to_str().unwrap(), "--min-edit-days", "0"])
+        .assert()
+        .success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let golden = std::fs::read_to_string("tests/golden/min_edit_days_0/users_per_day.csv").unwrap();
+    assert!(output == golden);
+}
+
+#[test]
+fn bus_factor() {
+    let (output_dir, assert) = run(&["--bus-factor"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let mut lines = output.lines();
+    assert!(lines.next().unwrap().ends_with(",bus_factor"));
+    // alice edits on the most days in every window
+    assert!(lines.all(|line| line.ends_with(",1")), "{}", output);
+}
+
+#[test]
+fn inequality_metrics() {
+    let (output_dir, assert) = run(&["--inequality-metrics"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.lines().next().unwrap().ends_with(",gini,theil"));
+    // Only 1 user, so they're equal
+    assert!(output.contains("\n2023-01-01,1,1,0,100.0,0.0,0,0.0,0.0\n"));
+    // bob edits on 13 days, and alice on 25
+    assert!(
+        output.contains("\n2023-02-28,1,2,1,100.0,55.5,0,0.1579,0.0507\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn inequality_weight() {
+    let last_line = |weight_args: &[&str]| {
+        let (output_dir, assert) =
+            run(&[&["--inequality-metrics", "--bus-factor"], weight_args].concat());
+        assert.success();
+        let output =
+            std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+        output.lines().last().unwrap().to_owned()
+    };
+    assert_eq!(
+        last_line(&["--weight", "edits"]),
+        "2023-04-10,1,3,1,100.0,94.0,2,1,0.3966,0.3191"
+    );
+    assert_eq!(
+        last_line(&["--weight", "days"]),
+        "2023-04-10,1,3,1,100.0,94.0,2,1,0.4103,0.3534"
+    );
+    // Every edit day in this file has its own changeset
+    assert_eq!(
+        last_line(&["--weight", "changesets"]),
+        last_line(&["--weight", "days"])
+    );
+    // The default
+    assert_eq!(last_line(&[]), last_line(&["--weight", "days"]));
+}
+
+#[test]
+fn normalize() {
+    let (output_dir, assert) = run(&["--normalize"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.starts_with(
+        "date,num_users,rolling_yr_total,users_ge42_days,pct_of_alltime_contributors,\
+         median_account_age_days,at_risk_users,users_per_1k_edits\n\
+         2023-01-01,1,1,0,100.0,0.0,0,1000.0\n\
+         2023-01-02,1,1,0,100.0,1.0,0,500.0\n"
+    ));
+}
+
+/// bob deletes a node & a way, which carol then recreates.
+#[test]
+fn track_restorations() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/restorations.osh.pbf",
+        &["--track-restorations", "--min-edit-days", "0"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.lines().next().unwrap().ends_with(",restorations"));
+    assert!(output.contains("\n2023-01-03,2,1,bob,no,2023-01-02,no,1,1.0,burst,0\n"));
+    assert!(output.contains("\n2023-01-03,3,1,carol,no,2023-01-03,no,0,1.0,burst,2\n"));
+}
+
+/// bob adds a name, carol moves the node, then moves it and changes its tags, and restores a
+/// deleted way, which isn't counted.
+#[test]
+fn track_tag_changes() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/tag_changes.osh.pbf",
+        &["--track-tag-changes", "--min-edit-days", "0"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("tag_changes.csv")).unwrap();
+    assert_eq!(
+        output,
+        "uid,username,tag_edits,geometry_edits\n1,alice,0,0\n2,bob,1,0\n3,carol,1,2\n"
+    );
+}
+
+/// carol makes 3 edits, on 2 days.
+#[test]
+fn active_by_edits() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/tag_changes.osh.pbf",
+        &[
+            "--active-by",
+            "edits",
+            "--active-threshold",
+            "3",
+            "--min-edit-days",
+            "0",
+        ],
+    );
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.starts_with("date,num_users,rolling_yr_total,users_ge3_edits,"),
+        "{}",
+        output
+    );
+    assert!(output.contains("\n2023-01-03,1,3,0,"), "{}", output);
+    assert!(output.contains("\n2023-01-04,1,3,1,"), "{}", output);
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.starts_with("date,uid,num_edit_days_last_yr,username,ge3edits,"));
+    assert!(
+        output.contains("\n2023-01-04,3,2,carol,yes,\"2023-01-03,2023-01-04\",no,1,1.0,burst\n"),
+        "{}",
+        output
+    );
+}
+
+/// Only carol's day with 1 edit counts, so her 2 edits on the day before aren't in her edit count.
+#[test]
+fn active_by_edits_without_removed_days() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/tag_changes.osh.pbf",
+        &[
+            "--active-day-expr",
+            "edit_count < 2",
+            "--active-by",
+            "edits",
+            "--active-threshold",
+            "2",
+            "--min-edit-days",
+            "0",
+        ],
+    );
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert_eq!(output.lines().nth(1), Some("2023-01-04,1,1,0,100.0,0.0,0"));
+}
+
+/// carol edits the node twice, and the way once.
+#[test]
+fn track_objects() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/tag_changes.osh.pbf",
+        &["--track-objects", "--min-edit-days", "0"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.lines().next().unwrap().ends_with(",objects_touched"));
+    assert!(
+        output.contains("\n2023-01-04,3,2,carol,no,\"2023-01-03,2023-01-04\",no,1,1.0,burst,2\n"),
+        "{}",
+        output
+    );
+}
+
+/// carol's node has `amenity` & `name`, and her way `highway`.
+#[test]
+fn track_tag_keys() {
+    for (max_tag_keys, distinct_tag_keys) in [(&[][..], 3), (&["--max-tag-keys", "2"], 2)] {
+        let (output_dir, assert) = run_on(
+            "tests/fixtures/tag_changes.osh.pbf",
+            &[&["--track-tag-keys", "--min-edit-days", "0"], max_tag_keys].concat(),
+        );
+        assert.success();
+        let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+        assert!(output
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(",distinct_tag_keys"));
+        assert!(
+            output.contains(&format!(
+                "\n2023-01-04,3,2,carol,no,\"2023-01-03,2023-01-04\",no,1,1.0,burst,{}\n",
+                distinct_tag_keys
+            )),
+            "{}",
+            output
+        );
+    }
+}
+
+#[test]
+fn emit_events_jsonl() {
+    let events_dir = tempfile::tempdir().unwrap();
+    let events = events_dir.path().join("events.jsonl");
+    let (_output_dir, assert) = run(&["--emit-events-jsonl", events.to_str().unwrap()]);
+    assert.success();
+    let output = std::fs::read_to_string(&events).unwrap();
+    assert!(
+        output.starts_with("{\"uid\":1,\"date\":\"2023-01-01\",\"num_edits\":1}\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.ends_with("\n{\"uid\":3,\"date\":\"2023-04-10\",\"num_edits\":1}\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n{\"uid\":3,\"date\":\"2023-03-12\",\"num_edits\":2}\n"),
+        "{}",
+        output
+    );
+
+    // Only the missing days are added
+    let first_days: String = output
+        .lines()
+        .take_while(|line| !line.contains("2023-01-06"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    std::fs::write(&events, first_days).unwrap();
+    let (_output_dir, assert) = run(&["--emit-events-jsonl", events.to_str().unwrap(), "--append"]);
+    assert.success();
+    assert_eq!(std::fs::read_to_string(&events).unwrap(), output);
+}
+
+/// carol only edits on 2 days, fewer than `--min-edit-days`.
+#[test]
+fn user_calendars() {
+    let calendars_dir = tempfile::tempdir().unwrap();
+    let calendars = calendars_dir.path().join("calendars");
+    let (_output_dir, assert) = run(&[
+        "--user-calendars",
+        calendars.to_str().unwrap(),
+        "--end-date",
+        "2023-03-12",
+    ]);
+    assert.success();
+    let mut files: Vec<_> = std::fs::read_dir(&calendars)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["1.csv", "2.csv"]);
+    let output = std::fs::read_to_string(calendars.join("2.csv")).unwrap();
+    // Every day in the file, up to the end date
+    assert!(
+        output.starts_with("date,num_edits\n2023-01-01,0\n"),
+        "{}",
+        output
+    );
+    assert!(output.contains("\n2023-01-06,1\n"), "{}", output);
+    assert!(output.ends_with("\n2023-03-12,0\n"), "{}", output);
+    assert_eq!(output.lines().count(), 1 + 71);
+}
+
+/// alice & bob stop after February, and carol is still active in April, the last month.
+#[test]
+fn survival() {
+    let (output_dir, assert) = run(&["--survival"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("survival.csv")).unwrap();
+    assert_eq!(
+        output,
+        "months_since_first_edit,at_risk,survival\n0,3,1.0\n1,3,1.0\n2,3,0.3333\n"
+    );
+}
+
+#[test]
+fn since_first_edit_window() {
+    let (output_dir, assert) = run(&["--since-first-edit-window", "4"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("newcomer_activity.csv")).unwrap();
+    assert_eq!(
+        output,
+        "days_since_first,num_users,active_fraction\n0,3,1.0\n1,3,0.3333\n2,3,0.3333\n3,3,0.3333\n"
+    );
+
+    // Only alice's first edit is 99 days before the end of the input
+    let (output_dir, assert) = run(&["--since-first-edit-window", "400"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("newcomer_activity.csv")).unwrap();
+    assert!(output.ends_with("\n99,1,0.0\n"), "{}", output);
+}
+
+/// alice's & bob's nodes are in London, carol's in Cape Town. The way & relation don't count.
+#[test]
+fn geohash_grid() {
+    let (output_dir, assert) = run(&["--geohash-grid", "4"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("geohash_counts.csv")).unwrap();
+    assert_eq!(
+        output,
+        "geohash,distinct_users,edit_count\ngcpu,1,50\ngcpv,1,25\nk3vp,1,1\n"
+    );
+
+    let (output_dir, assert) = run(&["--geohash-grid", "1"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("geohash_counts.csv")).unwrap();
+    assert_eq!(output, "geohash,distinct_users,edit_count\ng,2,75\nk,1,1\n");
+
+    run(&["--geohash-grid", "13"]).1.failure();
+}
+
+#[test]
+fn emit_events() {
+    let (output_dir, assert) = run(&["--emit-events"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("edit_events.csv")).unwrap();
+    assert!(output.starts_with("uid,date\n1,2023-01-01\n"), "{}", output);
+    assert!(
+        output.ends_with("\n3,2023-03-12\n3,2023-04-10\n"),
+        "{}",
+        output
+    );
+}
+
+/// alice's node is in UTC+10, carol's in UTC-6, and dave's in no time zone. bob edits a way.
+#[test]
+fn timezone_per_region() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/timezones.osh.pbf",
+        &[
+            "--timezone-per-region",
+            "tests/fixtures/timezones.geojson",
+            "--emit-events",
+        ],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("edit_events.csv")).unwrap();
+    assert_eq!(
+        output,
+        "uid,date\n1,2023-01-02\n2,2023-01-01\n3,2023-01-01\n4,2023-01-01\n"
+    );
+}
+
+#[test]
+fn skip_empty_days() {
+    let (output_dir, assert) = run(&["--skip-empty-days", "--window-days", "5"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    // No one edits from 2023-02-25 to 2023-03-11, and the window is empty from 2023-03-06
+    assert!(output.contains("\n2023-02-28,1,1,"), "{}", output);
+    assert!(output.contains("\n2023-03-06,0,0,"), "{}", output);
+    assert!(!output.contains("\n2023-03-07,"), "{}", output);
+    assert!(output.contains("\n2023-03-12,1,1,"), "{}", output);
+}
+
+/// Only carol edits on more than one day.
+#[test]
+fn cohort_quality() {
+    let (output_dir, assert) = run_on("tests/fixtures/restorations.osh.pbf", &["--cohort-quality"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("cohort_quality.csv")).unwrap();
+    assert_eq!(
+        output,
+        "month,cohort_size,returned,pct_returned\n2023-01,3,1,33.33\n"
+    );
+}
+
+/// Appending to the start of the default output gives the whole default output.
+#[test]
+fn append() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let prefix = format!("{}/", output_dir.path().display());
+    let files = ["user_totals_per_day.csv", "users_per_day.csv"];
+    let write_start = || {
+        for file in files {
+            let golden =
+                std::fs::read_to_string(Path::new("tests/golden/default").join(file)).unwrap();
+            let start: String = golden
+                .split_inclusive('\n')
+                .take_while(|line| !line.starts_with("2023-02-"))
+                .collect();
+            std::fs::write(output_dir.path().join(file), start).unwrap();
+        }
+    };
+    write_start();
+    let mut cmd = Command::cargo_bin("osm-num-active-contributors").unwrap();
+    cmd.args(["-i", FIXTURE, "-p", &prefix, "--append"]);
+    cmd.assert().success();
+    for file in files {
+        let output = std::fs::read_to_string(output_dir.path().join(file)).unwrap();
+        let golden = std::fs::read_to_string(Path::new("tests/golden/default").join(file)).unwrap();
+        assert!(output == golden, "{} differs", file);
+    }
+
+    // The new rows have a bus_factor column, which the file doesn't have
+    write_start();
+    cmd.arg("--bus-factor")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("it has different columns"));
+}
+
+#[test]
+fn summary() {
+    let (_output_dir, assert) = run(&["--summary"]);
+    assert.success().stdout(predicates::str::contains(
+        "Peak day: 2 users on 2023-01-06\nPeak rolling total: 3 users on 2023-03-12\n",
+    ));
+}
+
+/// A version without any author information (as redacted ones can be) is skipped.
+#[test]
+fn redacted_objects() {
+    let (output_dir, assert) = run_on("tests/fixtures/redacted.o5m", &[]);
+    assert.success().stdout(predicates::str::contains(
+        "Skipped 1 redacted objects, without a uid or timestamp",
+    ));
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.contains("\n2023-01-03,1,2,"), "{}", output);
+}
+
+#[test]
+fn out_of_range_timestamp() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/bad_timestamp.osh.pbf",
+        &["--min-edit-days", "0"],
+    );
+    assert.success().stdout(predicates::str::contains(
+        "Skipped 1 redacted objects, without a uid or timestamp",
+    ));
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output.contains("\n2023-01-02,1,2,alice,"), "{}", output);
+    assert!(!output.contains("bob"), "{}", output);
+}
+
+#[test]
+fn lat_range() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/timezones.osh.pbf",
+        &[
+            "--min-edit-days",
+            "0",
+            "--min-lat",
+            "-35",
+            "--max-lat",
+            "-20",
+        ],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    // bob only edited a way, with alice's node
+    let users: Vec<_> = output
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(3).unwrap())
+        .collect();
+    assert_eq!(users, ["alice", "bob"], "{}", output);
+}
+
+#[test]
+fn precise_timestamps() {
+    let (output_dir, assert) = run(&["--precise-timestamps"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",first_edit_ts,last_edit_ts"));
+    assert!(output
+        .lines()
+        .nth(1)
+        .unwrap()
+        .ends_with(",2023-01-01T09:00:00Z,2023-02-19T09:00:00Z"));
+}
+
+#[test]
+fn autocorrelation() {
+    let (_output_dir, assert) = run(&["--autocorrelation"]);
+    assert.success().stdout(
+        predicates::str::contains("Autocorrelation of users per day, lag 7 days: 0.472\n").and(
+            predicates::str::contains(
+                "Autocorrelation of users per day, lag 365 days: none, the input is too short or constant\n",
+            ),
+        ),
+    );
+}
+
+#[test]
+fn retention_n_months() {
+    let (_output_dir, assert) = run(&["--retention-n-months", "1"]);
+    assert.success().stdout(predicates::str::contains(
+        "Retention after 1 months: 66.67% (average over 3 months)\n",
+    ));
+}
+
+#[test]
+fn funnel() {
+    let (_output_dir, assert) = run(&["--funnel"]);
+    assert.success().stdout(predicates::str::contains(
+        "Funnel: 3 users edited on at least 1 day\n\
+         Funnel: 3 users (100%) with at least 7 days between first & last edit\n\
+         Funnel: 2 users (66.67%) with at least 30 days between first & last edit\n\
+         Funnel: 0 users (0%) with at least 365 days between first & last edit\n",
+    ));
+}
+
+#[test]
+fn print_run_hash() {
+    let run_hash = |extra_args: &[&str]| {
+        let (_output_dir, assert) = run(&[&["--print-run-hash"], extra_args].concat());
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix("Run hash: "))
+            .unwrap()
+            .to_string()
+    };
+    // Each run has a different output directory
+    assert_eq!(run_hash(&[]), run_hash(&[]));
+    assert_ne!(run_hash(&[]), run_hash(&["--min-edit-days", "3"]));
+}
+
+#[test]
+fn resample() {
+    let (output_dir, assert) = run(&["--resample", "month"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("resampled.csv")).unwrap();
+    assert_eq!(
+        output,
+        "period,num_users,rolling_yr_total\n2023-01-01,2,2\n2023-02-01,2,2\n2023-03-01,1,3\n\
+         2023-04-01,1,3\n"
+    );
+
+    // No one edits from 2023-03-12 to 2023-04-09, but those weeks are still there
+    let (output_dir, assert) = run(&["--resample", "week", "--window-days", "7"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("resampled.csv")).unwrap();
+    assert_eq!(output.lines().count(), 17);
+    assert!(output.contains("\n2023-03-20,0,0\n"), "{}", output);
+}
+
+#[test]
+fn dates_file() {
+    let dates_dir = tempfile::tempdir().unwrap();
+    let dates_path = dates_dir.path().join("dates.txt");
+    std::fs::write(
+        &dates_path,
+        "# monthly\n2023-02-01\n\n2023-03-01\n2030-01-01\n",
+    )
+    .unwrap();
+    let (output_dir, assert) = run(&[
+        "--dates-file",
+        dates_path.to_str().unwrap(),
+        "--min-edit-days",
+        "0",
+    ]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    let dates: Vec<_> = output.lines().skip(1).map(|line| &line[..10]).collect();
+    assert_eq!(dates, ["2023-02-01", "2023-03-01"]);
+    // The same numbers as without the dates file
+    assert!(
+        output.contains("\n2023-03-01,0,2,1,100.0,56.5,0\n"),
+        "{}",
+        output
+    );
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(output.lines().count(), 5, "{}", output);
+
+    std::fs::write(&dates_path, "2023-02-30\n").unwrap();
+    let (_output_dir, assert) = run(&["--dates-file", dates_path.to_str().unwrap()]);
+    assert
+        .failure()
+        .stderr(predicates::str::contains("dates.txt:1: expected a date"));
+}
+
+#[test]
+fn profile_memory() {
+    let (_output_dir, assert) = run(&["--profile-memory"]);
+    let expected = if cfg!(target_os = "linux") {
+        "Peak memory use: "
+    } else {
+        "Peak memory use isn't known on this platform"
+    };
+    assert.success().stdout(predicates::str::contains(expected));
+}
+
+#[test]
+fn explain() {
+    let (output_dir, assert) = run(&["--explain", "--window-mode", "active-days"]);
+    let output = assert.success().get_output().stdout.clone();
+    let explained: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(explained["window_mode"], "active-days");
+    assert_eq!(explained["min_edit_days"], 20);
+    assert_eq!(explained["timezone"], "+00:00");
+    assert_eq!(explained["exclude_bots_by_name"], serde_json::Value::Null);
+    // Nothing is read or written
+    assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn preset() {
+    let explain = |extra_args: &[&str]| {
+        let mut args = vec!["--explain", "--preset", "osmstats"];
+        args.extend(extra_args);
+        let (_output_dir, assert) = run(&args);
+        let output = assert.success().get_output().stdout.clone();
+        serde_json::from_slice::<serde_json::Value>(&output).unwrap()
+    };
+    let explained = explain(&[]);
+    assert_eq!(explained["window_days"], 365);
+    assert_eq!(explained["active_threshold"], 42);
+    let explained = explain(&["--window-days", "30"]);
+    assert_eq!(explained["window_days"], 30);
+    assert_eq!(explained["active_threshold"], 42);
+}
+
+#[test]
+fn window_too_long() {
+    for window in ["99999999999999999y", "1000001"] {
+        run(&["--window-days", window])
+            .1
+            .code(2)
+            .stderr(predicates::str::contains(
+                "window can't be longer than 1000000 days",
+            ));
+    }
+    // The whole history is in every window
+    let (output_dir, assert) = run(&["--window-days", "1000000"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.ends_with("\n2023-04-10,1,3,1,100.0,94.0,2\n"),
+        "{}",
+        output
+    );
+}
+
+/// On 2023-01-06, bob starts editing.
+#[test]
+fn baseline_date() {
+    let (output_dir, assert) = run(&["--baseline-date", "2023-01-06"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",rolling_yr_index"));
+    assert!(
+        output.contains("\n2023-01-05,1,1,0,100.0,4.0,0,50.0\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n2023-01-06,2,2,0,100.0,2.5,0,100.0\n"),
+        "{}",
+        output
+    );
+    assert!(output.ends_with(",150.0\n"), "{}", output);
+
+    let (_output_dir, assert) = run(&["--baseline-date", "2022-01-01"]);
+    assert.failure().stderr(predicates::str::contains(
+        "--baseline-date 2022-01-01 is outside the dates in the input",
+    ));
+}
+
+/// alice creates the node & way, bob & carol only edit them.
+#[test]
+fn creators() {
+    let (output_dir, assert) = run_on("tests/fixtures/tag_changes.osh.pbf", &["--creators"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",creators,maintainers"));
+    assert!(
+        output.contains("\n2023-01-01,1,1,0,100.0,0.0,0,1,0\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.ends_with("\n2023-01-04,1,3,0,100.0,2.0,0,1,2\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn sticky_new_users() {
+    let (output_dir, assert) = run(&["--sticky-threshold", "2"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",sticky_new_users"));
+    // alice & bob edit on many more days, but carol only on 1
+    assert!(
+        output.contains("\n2023-01-01,1,1,0,100.0,0.0,0,1\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n2023-01-06,2,2,0,100.0,2.5,0,1\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n2023-03-12,1,3,1,100.0,65.0,0,0\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn limit_days() {
+    let (output_dir, assert) = run(&["--head", "3", "--min-edit-days", "0"]);
+    assert.success();
+    for filename in ["user_totals_per_day.csv", "users_per_day.csv"] {
+        let output = std::fs::read_to_string(output_dir.path().join(filename)).unwrap();
+        let mut dates: Vec<_> = output.lines().skip(1).map(|line| &line[..10]).collect();
+        dates.dedup();
+        assert_eq!(
+            dates,
+            ["2023-01-01", "2023-01-02", "2023-01-03"],
+            "{}",
+            filename
+        );
+    }
+}
+
+/// 2023-01-01 is a Sunday, in the last ISO week of 2022.
+#[test]
+fn isoweek() {
+    let (output_dir, assert) = run(&["--isoweek"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("isoweek.csv")).unwrap();
+    assert!(
+        output.starts_with("iso_year,iso_week,active_users,new_users\n2022,52,1,1\n2023,1,2,1\n"),
+        "{}",
+        output
+    );
+    assert!(output.contains("\n2023,12,0,0\n"), "{}", output);
+    assert!(output.ends_with("\n2023,15,1,0\n"), "{}", output);
+}
+
+#[test]
+fn no_rolling() {
+    let (output_dir, assert) = run(&["--no-rolling"]);
+    assert.success();
+    assert!(!output_dir.path().join("users_per_day.csv").exists());
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.starts_with("date,num_users\n2023-01-01,1\n"),
+        "{}",
+        output
+    );
+    assert_eq!(output.lines().count(), 101);
+}
+
+/// alice's node is in A, carol's in B, and bob's in a hole of A.
+/// bob's node is in the hole of A, & carol's in B.
+#[test]
+fn region_timeseries() {
+    let (output_dir, assert) = run(&[
+        "--regions",
+        "tests/fixtures/countries.geojson",
+        "--region-timeseries",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("region_timeseries.csv")).unwrap();
+    assert!(
+        output.starts_with(
+            "region,date,active_users,rolling_yr_total\n\
+             \"A, with a hole around bob's node\",2023-01-01,1,1\n"
+        ),
+        "{}",
+        output
+    );
+    for row in [
+        "\"A, with a hole around bob's node\",2023-01-11,1,1",
+        "\"A, with a hole around bob's node\",2023-04-10,0,1",
+        "B,2023-03-11,0,0",
+        "B,2023-03-12,1,1",
+        "B,2023-04-10,0,1",
+    ] {
+        assert!(
+            output.contains(&format!("\n{}\n", row)),
+            "{}: {}",
+            row,
+            output
+        );
+    }
+    // 100 days, for each of the 2 regions
+    assert_eq!(output.lines().count(), 1 + 2 * 100);
+}
+
+#[test]
+fn countries() {
+    let (output_dir, assert) = run(&[
+        "--countries",
+        "tests/fixtures/countries.geojson",
+        "--min-edit-days",
+        "0",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let mut reader = csv::Reader::from_reader(output.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+    let (uid, countries_edited) = (column("uid"), column("countries_edited"));
+    let rows: Vec<_> = reader.records().map(|row| row.unwrap()).collect();
+    for (user, expected) in [("1", "1"), ("2", "0"), ("3", "1")] {
+        assert!(rows.iter().any(|row| row[uid] == *user));
+        assert!(rows
+            .iter()
+            .filter(|row| row[uid] == *user)
+            .all(|row| row[countries_edited] == *expected));
+    }
+}
+
+/// A 10 KB username is cut off.
+#[test]
+fn long_username() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/long_username.osh.pbf",
+        &["--min-edit-days", "0"],
+    );
+    assert.success().stderr(predicates::str::contains(
+        "Warning: uid 1 has a username longer than 255 characters",
+    ));
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    let username = format!("{}…", "x".repeat(255));
+    assert!(
+        output.contains(&format!("\n2023-01-01,1,1,{},", username)),
+        "{}",
+        output
+    );
+}
+
+/// A username with a newline, quotes & a comma is quoted, so it's read back as it was.
+#[test]
+fn quoted_username() {
+    let username = "eve \"the\nmapper\", jr";
+    let profiles_dir = tempfile::tempdir().unwrap();
+    let profiles = profiles_dir.path().join("profiles.jsonl");
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/quoted_username.osh.pbf",
+        &[
+            "--min-edit-days",
+            "0",
+            "--profiles",
+            profiles.to_str().unwrap(),
+        ],
+    );
+    assert.success();
+    let mut reader = csv::Reader::from_path(output_dir.path().join("users_per_day.csv")).unwrap();
+    let username_col = reader
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|col| col == "username")
+        .unwrap();
+    let rows: Vec<csv::StringRecord> = reader.records().map(|row| row.unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][username_col], username);
+
+    let profile: serde_json::Value =
+        serde_json::from_str(std::fs::read_to_string(&profiles).unwrap().trim_end()).unwrap();
+    assert_eq!(profile["username"], username);
+}
+
+/// alice saves node 1 three times on the 1st, and also edits node 2.
+#[test]
+fn dedupe_same_day_object() {
+    let events_dir = tempfile::tempdir().unwrap();
+    let events = events_dir.path().join("events.jsonl");
+    let events = events.to_str().unwrap();
+    let (_output_dir, assert) = run_on(
+        "tests/fixtures/same_day_versions.osh.pbf",
+        &["--emit-events-jsonl", events],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(events).unwrap();
+    assert!(
+        output.starts_with("{\"uid\":1,\"date\":\"2023-01-01\",\"num_edits\":4}\n"),
+        "{}",
+        output
+    );
+
+    let (_output_dir, assert) = run_on(
+        "tests/fixtures/same_day_versions.osh.pbf",
+        &["--emit-events-jsonl", events, "--dedupe-same-day-object"],
+    );
+    assert.success();
+    let output = std::fs::read_to_string(events).unwrap();
+    assert_eq!(
+        output,
+        concat!(
+            "{\"uid\":1,\"date\":\"2023-01-01\",\"num_edits\":2}\n",
+            "{\"uid\":1,\"date\":\"2023-01-02\",\"num_edits\":1}\n",
+            "{\"uid\":2,\"date\":\"2023-01-02\",\"num_edits\":1}\n",
+        )
+    );
+}
+
+#[test]
+fn compare_windows() {
+    let (_output_dir, assert) = run(&[
+        "--window-days",
+        "30",
+        "--compare-windows",
+        "2023-02-19",
+        "2023-04-10",
+    ]);
+    // alice & bob stopped in the window before the 2nd one, and only carol is new in it
+    assert.success().stdout(predicates::str::contains(concat!(
+        "Window ending   2023-02-19  2023-04-10  Change\n",
+        "Active users             2           1      -1\n",
+        "New users                0           1      +1\n",
+        "Churned users            0           2      +2\n",
+    )));
+
+    let (_output_dir, assert) = run(&["--compare-windows", "2023-02-19", "2024-02-19"]);
+    assert.failure().stderr(predicates::str::contains(
+        "--compare-windows 2024-02-19 is outside the dates in the input",
+    ));
+}
+
+#[test]
+fn validate() {
+    let (_output_dir, assert) = run(&["--validate", "--creators", "--bus-factor"]);
+    assert.success().stdout(predicates::str::contains(
+        "Validated user_totals_per_day.csv: no invariants are violated",
+    ));
+}
+
+/// The `pattern` column of the row of this date & uid.
+fn pattern_of(users_per_day: &str, date_uid: &str) -> String {
+    let row = users_per_day
+        .lines()
+        .find(|line| line.starts_with(date_uid))
+        .unwrap_or_else(|| panic!("no row {} in {}", date_uid, users_per_day));
+    row.rsplit(',').next().unwrap().to_owned()
+}
+
+#[test]
+fn edit_patterns() {
+    let (output_dir, assert) = run(&["--min-edit-days", "0"]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(pattern_of(&output, "2023-01-05,1,"), "burst");
+    // alice edits every day, bob on every 2nd day
+    assert_eq!(pattern_of(&output, "2023-02-19,1,"), "daily");
+    assert_eq!(pattern_of(&output, "2023-02-28,2,"), "weekly");
+    // carol's 2 edit days are 29 days apart
+    assert_eq!(pattern_of(&output, "2023-04-10,3,"), "sporadic");
+
+    let (output_dir, assert) = run(&[
+        "--min-edit-days",
+        "0",
+        "--daily-min-density",
+        "0.4",
+        "--weekly-max-gap-days",
+        "30",
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert_eq!(pattern_of(&output, "2023-02-28,2,"), "daily");
+    assert_eq!(pattern_of(&output, "2023-04-10,3,"), "weekly");
+}
+
+#[test]
+fn population() {
+    let population_dir = tempfile::tempdir().unwrap();
+    let population = population_dir.path().join("population.csv");
+    std::fs::write(
+        &population,
+        "region,population\n\"A, with a hole around bob's node\",2000\nB,400000\n",
+    )
+    .unwrap();
+    let (output_dir, assert) = run(&[
+        "--regions",
+        "tests/fixtures/countries.geojson",
+        "--region-timeseries",
+        "--population",
+        population.to_str().unwrap(),
+    ]);
+    assert.success();
+    let output = std::fs::read_to_string(output_dir.path().join("region_timeseries.csv")).unwrap();
+    assert!(
+        output.starts_with("region,date,active_users,rolling_yr_total,active_per_100k\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n\"A, with a hole around bob's node\",2023-04-10,0,1,50.0\n"),
+        "{}",
+        output
+    );
+    assert!(output.ends_with("\nB,2023-04-10,0,1,0.25\n"), "{}", output);
+
+    std::fs::write(&population, "region,population\nB,400000\n").unwrap();
+    let (_output_dir, assert) = run(&[
+        "--regions",
+        "tests/fixtures/countries.geojson",
+        "--region-timeseries",
+        "--population",
+        population.to_str().unwrap(),
+    ]);
+    assert.failure().stderr(predicates::str::contains(
+        "Region A, with a hole around bob's node isn't in the --population file",
+    ));
+}
+
+#[test]
+fn warn_on_clock_skew() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/future.osh.pbf",
+        &[
+            "--warn-on-clock-skew",
+            "--clock-skew-before",
+            "2023-06-01",
+            "--min-edit-days",
+            "0",
+        ],
+    );
+    assert
+        .success()
+        .stderr(predicates::str::contains(
+            "Warning: 1 objects have a timestamp before 2023-06-01, e.g. node 1 v1 at \
+             2023-01-01T09:00:00Z\n",
+        ))
+        .stderr(predicates::str::contains(
+            "Warning: 2 objects have a timestamp after the replication timestamp, \
+             2024-01-01T12:00:00Z, e.g. node 2 v1 at 2024-01-01T13:00:00Z, \
+             node 2 v2 at 2031-05-01T09:00:00Z\n",
+        ));
+    // They're still counted
+    let totals =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(totals.contains("\n2031-05-01,1,"), "{}", totals);
+}
+
+#[test]
+fn events_bin_round_trip() {
+    let events_dir = tempfile::tempdir().unwrap();
+    let events = events_dir.path().join("events.bin");
+    let events = events.to_str().unwrap();
+    let (_output_dir, assert) = run(&["--emit-events-bin", events]);
+    assert.success();
+    assert!(std::fs::read(events).unwrap().starts_with(b"OSMNACEV\x01"));
+
+    // Recalculating with another window gives the same numbers as reading the OSM file again
+    let (osm_dir, assert) = run(&["--window-days", "30"]);
+    assert.success();
+    let events_output_dir = tempfile::tempdir().unwrap();
+    let prefix = format!("{}/", events_output_dir.path().display());
+    Command::cargo_bin("osm-num-active-contributors")
+        .unwrap()
+        .args([
+            "--events-input",
+            events,
+            "-p",
+            &prefix,
+            "--window-days",
+            "30",
+        ])
+        .assert()
+        .success();
+    let read = |dir: &Path| std::fs::read_to_string(dir.join("user_totals_per_day.csv")).unwrap();
+    assert_eq!(read(events_output_dir.path()), read(osm_dir.path()));
+
+    Command::cargo_bin("osm-num-active-contributors")
+        .unwrap()
+        .args([
+            "--events-input",
+            "tests/fixtures/small.osh.pbf",
+            "-p",
+            &prefix,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "tests/fixtures/small.osh.pbf isn't an --emit-events-bin file",
+        ));
+}
+
+/// There are no edit counts in an events file, which these need.
+#[test]
+fn events_input_without_edits() {
+    let events_dir = tempfile::tempdir().unwrap();
+    let events = events_dir.path().join("events.bin");
+    let events = events.to_str().unwrap();
+    let (_output_dir, assert) = run(&["--emit-events-bin", events]);
+    assert.success();
+    let jsonl = events_dir.path().join("events.jsonl");
+    let calendars = events_dir.path().join("calendars");
+    for (arg, path) in [
+        ("--emit-events-jsonl", &jsonl),
+        ("--user-calendars", &calendars),
+    ] {
+        Command::cargo_bin("osm-num-active-contributors")
+            .unwrap()
+            .args([
+                "--events-input",
+                events,
+                "-p",
+                &format!("{}/", events_dir.path().display()),
+                arg,
+                path.to_str().unwrap(),
+            ])
+            .assert()
+            .code(2)
+            .stderr(predicates::str::contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn split_anonymous() {
+    let (output_dir, assert) = run_on(
+        "tests/fixtures/anonymous.osh.pbf",
+        &["--split-anonymous", "--min-edit-days", "0"],
+    );
+    assert
+        .success()
+        .stdout(predicates::str::contains("Have 1 users & 2 days"));
+    let output = std::fs::read_to_string(output_dir.path().join("anonymous_per_day.csv")).unwrap();
+    assert_eq!(
+        output,
+        "date,registered_edits,anonymous_edits,pct_anonymous\n\
+         2007-01-01,0,1,100.0\n\
+         2007-01-02,1,1,50.0\n\
+         2007-01-03,1,0,0.0\n"
+    );
+    let output = std::fs::read_to_string(output_dir.path().join("users_per_day.csv")).unwrap();
+    assert!(!output.contains("(anonymous)"), "{}", output);
+}
+
+#[test]
+fn flag_outlier_days() {
+    let (output_dir, assert) = run(&["--flag-outlier-days", "1.5"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.starts_with(
+        "date,num_users,rolling_yr_total,users_ge42_days,pct_of_alltime_contributors,\
+         median_account_age_days,at_risk_users,is_outlier\n\
+         2023-01-01,1,1,0,100.0,0.0,0,no\n"
+    ));
+    // bob's first edit is the first day with 2 users, the median before is 1
+    assert!(
+        output.contains("\n2023-01-06,2,2,0,100.0,2.5,0,yes\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n2023-01-07,1,2,0,100.0,3.5,0,no\n"),
+        "{}",
+        output
+    );
+
+    let (output_dir, assert) = run(&["--flag-outlier-days", "2"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(
+        output.contains("\n2023-01-06,2,2,0,100.0,2.5,0,no\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn type_mix() {
+    let (output_dir, assert) = run(&["--type-mix"]);
+    assert.success();
+    let output =
+        std::fs::read_to_string(output_dir.path().join("user_totals_per_day.csv")).unwrap();
+    assert!(output.starts_with(
+        "date,num_users,rolling_yr_total,users_ge42_days,pct_of_alltime_contributors,\
+         median_account_age_days,at_risk_users,node_only_users,way_relation_only_users,\
+         mixed_type_users\n\
+         2023-01-01,1,1,0,100.0,0.0,0,1,0,0\n"
+    ));
+    // bob starts with a way, and edits a node later
+    assert!(
+        output.contains("\n2023-01-06,2,2,0,100.0,2.5,0,1,1,0\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("\n2023-01-11,2,2,0,100.0,7.5,0,1,0,1\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn uid_shards() {
+    let events_dir = tempfile::tempdir().unwrap();
+    let mut events_input = Vec::new();
+    for (shard, num_users) in [("0/2", 1), ("1/2", 2)] {
+        let events = events_dir.path().join(format!("{}.bin", &shard[..1]));
+        let events = events.to_str().unwrap().to_owned();
+        let (_output_dir, assert) = run(&["--uid-shard", shard, "--emit-events-bin", &events]);
+        assert.success().stdout(predicates::str::contains(format!(
+            "Have {} users",
+            num_users
+        )));
+        events_input.extend(["--events-input".to_owned(), events]);
+    }
+
+    // The merged shards are the same as reading everything at once
+    let (osm_dir, assert) = run(&[]);
+    assert.success();
+    let merged_dir = tempfile::tempdir().unwrap();
+    let prefix = format!("{}/", merged_dir.path().display());
+    Command::cargo_bin("osm-num-active-contributors")
+        .unwrap()
+        .args(&events_input)
+        .args(["-p", &prefix])
+        .assert()
+        .success();
+    let read = |dir: &Path| std::fs::read_to_string(dir.join("user_totals_per_day.csv")).unwrap();
+    assert_eq!(read(merged_dir.path()), read(osm_dir.path()));
+
+    let (_output_dir, assert) = run(&["--uid-shard", "2/2"]);
+    assert.failure().stderr(predicates::str::contains(
+        "shard 2 doesn't exist, there are only shards 0 to 1",
+    ));
+}